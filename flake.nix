@@ -97,7 +97,7 @@
           passwordFile = lib.mkOption {
             type = lib.types.nullOr lib.types.path;
             default = null;
-            description = "Path to file containing password for Basic authentication.";
+            description = "Path to file containing password for Basic authentication, re-read when it changes.";
           };
 
           topProcesses = lib.mkOption {
@@ -105,6 +105,12 @@
             default = 10;
             description = "Number of top processes to report by CPU and memory (0 to disable).";
           };
+
+          configFile = lib.mkOption {
+            type = lib.types.nullOr lib.types.path;
+            default = null;
+            description = "Path to a TOML config file, command line options take precedence.";
+          };
         };
 
         config = lib.mkIf cfg.enable {
@@ -115,10 +121,7 @@
             };
 
             Service = {
-              Type = "simple";
-              EnvironmentFile = lib.optionals (cfg.passwordFile != null) [
-                cfg.passwordFile
-              ];
+              Type = "notify";
               ExecStart = let
                 args =
                   [
@@ -132,10 +135,20 @@
                   ++ lib.optionals (cfg.username != null) [
                     "--username"
                     cfg.username
+                  ]
+                  ++ lib.optionals (cfg.passwordFile != null) [
+                    "--password-file"
+                    (toString cfg.passwordFile)
+                  ]
+                  ++ lib.optionals (cfg.configFile != null) [
+                    "--config"
+                    (toString cfg.configFile)
                   ];
               in "${cfg.package}/bin/agemon ${lib.escapeShellArgs args}";
               Restart = "on-failure";
               RestartSec = 5;
+              # Longer than a push with all its retries, see the README
+              WatchdogSec = 300;
             };
 
             Install = {
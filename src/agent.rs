@@ -0,0 +1,308 @@
+use std::{
+    io::{self, Write},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use miette::{IntoDiagnostic, Result, miette};
+use prometheus_remote_write::TimeSeries;
+use sysinfo::{MINIMUM_CPU_UPDATE_INTERVAL, Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    args::Args,
+    collector::{Registry, Sink},
+    descriptors::{self, MetricDescriptor},
+    endpoint::{Endpoint, EndpointSettings},
+    exporter::{self, Exporter},
+    notify::Notifier,
+    relabel::relabel,
+    retry::RetryPolicy,
+    shutdown::Shutdown,
+    staleness::StalenessTracker,
+};
+
+/// Runs the collectors of a registry on every interval and ships the results to the remote write
+/// endpoints and the scrape endpoint as configured.
+pub struct Agent {
+    args: Args,
+    registry: Registry,
+    endpoints: Vec<Endpoint>,
+    exporter: Option<Exporter>,
+    descriptors: Arc<[MetricDescriptor]>,
+    staleness: StalenessTracker,
+    /// Latest series of every source for the scrape endpoint, in collection order
+    latest: Vec<(String, Vec<TimeSeries>)>,
+    /// The agent's own process, for its memory and CPU usage
+    process: Option<(Pid, System)>,
+    shutdown: Shutdown,
+}
+
+impl Agent {
+    pub fn new(args: Args, mut registry: Registry) -> Result<Self> {
+        // Inside a container the system hostname is the container's
+        if let Some(hostname) = args
+            .hostname
+            .clone()
+            .or_else(|| args.host_paths().hostname())
+        {
+            registry.set_hostname(hostname);
+        }
+        registry.set_external_labels(args.labels.clone());
+        registry.set_default_interval(Duration::from_secs(args.interval));
+
+        let mut descriptors: Vec<MetricDescriptor> = registry.descriptors().copied().collect();
+        descriptors.extend_from_slice(descriptors::AGENT);
+        if args.pushes() {
+            descriptors.extend_from_slice(descriptors::REMOTE_WRITE);
+            if args.buffer_dir.is_some() {
+                descriptors.extend_from_slice(descriptors::BUFFER);
+            }
+        }
+
+        let descriptors: Arc<[MetricDescriptor]> = descriptors.into();
+        let exporter = args
+            .listen_address
+            .filter(|_| args.command.is_none())
+            .map(|addr| Exporter::spawn(addr, descriptors.to_vec()))
+            .transpose()?;
+
+        let settings = EndpointSettings {
+            policy: RetryPolicy {
+                max_retries: args.max_retries,
+                min_backoff: Duration::from_millis(args.retry_min_backoff_ms),
+                max_backoff: Duration::from_millis(args.retry_max_backoff_ms),
+            },
+            metadata_interval: Duration::from_secs(args.metadata_interval),
+            descriptors: descriptors.clone(),
+            buffer_max_bytes: args.buffer_max_size_mb * 1024 * 1024,
+            buffer_max_age: Duration::from_secs(args.buffer_max_age),
+        };
+        let shutdown = Shutdown::new();
+        let endpoints = args
+            .endpoints()
+            .into_iter()
+            .map(|(name, config)| {
+                // Named endpoints each get their own subdirectory
+                let buffer_dir = args.buffer_dir.as_ref().map(|dir| {
+                    if args.remote_write.is_empty() {
+                        dir.clone()
+                    } else {
+                        dir.join(&name)
+                    }
+                });
+                Endpoint::spawn(name, config, buffer_dir, settings.clone(), shutdown.clone())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Agent {
+            args,
+            registry,
+            endpoints,
+            exporter,
+            descriptors,
+            staleness: StalenessTracker::new(),
+            latest: vec![],
+            process: sysinfo::get_current_pid()
+                .ok()
+                .map(|pid| (pid, System::new())),
+            shutdown,
+        })
+    }
+
+    /// Collect and push until SIGTERM or SIGINT, running every collector when it is due.
+    ///
+    /// On shutdown the batches already queued are still pushed, or written to the buffer, until
+    /// the shutdown timeout. Returns an error if it expired first or batches were lost.
+    ///
+    /// When started by systemd with `Type=notify`, reports readiness after the first collection,
+    /// the push results as status and pings the watchdog while collection and pushes progress.
+    pub fn run(mut self) -> Result<()> {
+        self.shutdown.on_signals()?;
+        let mut notifier = Notifier::from_env();
+
+        let interval = Duration::from_secs(self.args.interval);
+        info!(
+            "starting agemon with interval: {}s, collectors: {}",
+            interval.as_secs(),
+            self.registry.names().collect::<Vec<_>>().join(", ")
+        );
+        let mut ready = false;
+        let mut next_due = Instant::now();
+        while !self.shutdown.is_triggered() {
+            if Instant::now() >= next_due {
+                match self.collect_and_push() {
+                    Ok(()) if !ready => {
+                        notifier.ready();
+                        ready = true;
+                    }
+                    Ok(()) => {}
+                    Err(err) => error!("task failed: {}", err),
+                }
+                next_due = self
+                    .registry
+                    .next_due()
+                    .unwrap_or_else(|| Instant::now() + interval);
+            }
+            notifier.status(self.status());
+
+            // Ping at half the timeout, as systemd recommends, unless a push is stuck
+            let mut wake = next_due;
+            if let Some(timeout) = notifier.watchdog_timeout() {
+                match self
+                    .endpoints
+                    .iter()
+                    .find(|endpoint| endpoint.stalled(timeout))
+                {
+                    Some(endpoint) => warn!(
+                        "{}: push stuck for over {:?}, skipping watchdog ping",
+                        endpoint.name(),
+                        timeout
+                    ),
+                    None => notifier.watchdog(),
+                }
+                wake = wake.min(Instant::now() + timeout / 2);
+            }
+            self.shutdown
+                .sleep(wake.saturating_duration_since(Instant::now()));
+        }
+        notifier.stopping();
+        self.close()
+    }
+
+    /// Last push result of every endpoint.
+    fn status(&self) -> String {
+        if self.endpoints.is_empty() {
+            return "collecting".to_string();
+        }
+        self.endpoints
+            .iter()
+            .map(Endpoint::status)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Wait for every endpoint to finish its queue, sharing one shutdown deadline.
+    fn close(self) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(self.args.shutdown_timeout);
+        let unfinished = self
+            .endpoints
+            .into_iter()
+            .map(|endpoint| endpoint.close(deadline))
+            .filter(|finished| !finished)
+            .count();
+        if unfinished > 0 {
+            return Err(miette!(
+                "{} endpoints did not push all queued batches before shutting down",
+                unfinished
+            ));
+        }
+        info!("shutdown complete");
+        Ok(())
+    }
+
+    /// Run every collector once and print the result in the configured format.
+    pub fn once(mut self) -> Result<()> {
+        // Usage percentages are computed between two refreshes, give them a measuring window
+        thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+        let timeseries = self.collect();
+        self.print(timeseries)
+    }
+
+    fn print(&self, timeseries: Vec<TimeSeries>) -> Result<()> {
+        let out = exporter::encode(self.args.format, timeseries, &self.descriptors);
+        io::stdout()
+            .lock()
+            .write_all(out.as_bytes())
+            .into_diagnostic()
+    }
+
+    /// Run every collector once and return their series, including the agent's own metrics.
+    pub fn collect(&mut self) -> Vec<TimeSeries> {
+        let mut sink = self.registry.collect();
+        self.collect_agent_metrics(&mut sink);
+        relabel(sink.into_timeseries(), &self.args.metric_relabel_configs)
+    }
+
+    fn collect_agent_metrics(&mut self, sink: &mut Sink) {
+        sink.set_source("agent");
+        self.registry.collect_metrics(sink);
+
+        if let Some((pid, system)) = &mut self.process {
+            system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&[*pid]),
+                false,
+                ProcessRefreshKind::nothing().with_memory().with_cpu(),
+            );
+            if let Some(process) = system.process(*pid) {
+                // agemon_agent_resident_memory_bytes: Resident memory of the agent in bytes
+                sink.push(
+                    "agemon_agent_resident_memory_bytes",
+                    process.memory() as f64,
+                );
+
+                // agemon_agent_cpu_seconds_total: CPU time used by the agent in seconds (counter)
+                sink.push(
+                    "agemon_agent_cpu_seconds_total",
+                    process.accumulated_cpu_time() as f64 / 1000.0,
+                );
+            }
+        }
+
+        for endpoint in &self.endpoints {
+            endpoint.collect_metrics(sink);
+        }
+    }
+
+    /// Run the collectors that are due and queue their series as one batch for every endpoint.
+    pub fn collect_and_push(&mut self) -> Result<()> {
+        let mut sink = self.registry.collect_due(Instant::now());
+        self.collect_agent_metrics(&mut sink);
+        let timestamp = sink.timestamp();
+        let pushing = !self.endpoints.is_empty() || self.args.dry_run;
+
+        let mut timeseries = vec![];
+        let mut stale = vec![];
+        for (source, series) in sink.into_sources() {
+            let series = relabel(series, &self.args.metric_relabel_configs);
+            if pushing {
+                stale.extend(self.staleness.update(&source, &series, timestamp));
+            }
+            if self.exporter.is_some() {
+                match self.latest.iter_mut().find(|(name, _)| *name == source) {
+                    Some((_, latest)) => *latest = series.clone(),
+                    None => self.latest.push((source, series.clone())),
+                }
+            }
+            timeseries.extend(series);
+        }
+        info!("collected {} metrics", timeseries.len());
+
+        if let Some(exporter) = &self.exporter {
+            exporter.update(
+                self.latest
+                    .iter()
+                    .flat_map(|(_, series)| series.iter().cloned())
+                    .collect(),
+            );
+        }
+        if !pushing {
+            return Ok(());
+        }
+
+        if !stale.is_empty() {
+            debug!("marking {} vanished series as stale", stale.len());
+            timeseries.extend(stale);
+        }
+        if self.args.dry_run {
+            return self.print(timeseries);
+        }
+
+        let timeseries = Arc::new(timeseries);
+        for endpoint in &self.endpoints {
+            endpoint.send(timeseries.clone());
+        }
+        Ok(())
+    }
+}
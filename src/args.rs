@@ -0,0 +1,443 @@
+use std::{collections::BTreeMap, net::SocketAddr, path::PathBuf};
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, parser::ValueSource};
+use miette::{Result, miette};
+use tracing::warn;
+
+use crate::{
+    config::{
+        CollectorKind, CollectorsConfig, FileConfig, HostPaths, RelabelConfig, RemoteWriteConfig,
+        RemoteWriteProtocol, TlsConfig, is_valid_label_name,
+    },
+    exporter::OutputFormat,
+};
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Agent monitoring - push system metrics to Prometheus remote write"
+)]
+pub struct Args {
+    /// TOML config file, command line flags and environment variables take precedence
+    #[arg(short, long, env = "AGEMON_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Interval between metric collections in seconds
+    #[arg(short, long, default_value_t = 15, global = true)]
+    pub interval: u64,
+
+    /// Prometheus remote write endpoint URL
+    #[arg(
+        short,
+        long,
+        env = "AGEMON_REMOTE_WRITE_URL",
+        default_value_t = String::from("http://localhost:9090/api/v1/write"),
+        global = true
+    )]
+    pub remote_write_url: String,
+
+    /// Remote write protocol version, v2 needs a receiver supporting Remote Write 2.0
+    #[arg(
+        long,
+        env = "AGEMON_REMOTE_WRITE_PROTOCOL",
+        value_enum,
+        default_value_t = RemoteWriteProtocol::V1,
+        global = true
+    )]
+    pub remote_write_protocol: RemoteWriteProtocol,
+
+    /// Username for Basic authentication (optional)
+    #[arg(short, long, env = "AGEMON_REMOTE_WRITE_USERNAME", global = true)]
+    pub username: Option<String>,
+
+    /// Password for Basic authentication, prefer --password-file (optional)
+    #[arg(
+        short,
+        long,
+        env = "AGEMON_REMOTE_WRITE_PASSWORD",
+        hide_env_values = true,
+        global = true
+    )]
+    pub password: Option<String>,
+
+    /// File containing the password for Basic authentication, re-read whenever it changes
+    /// (optional)
+    #[arg(long, env = "AGEMON_REMOTE_WRITE_PASSWORD_FILE", global = true)]
+    pub password_file: Option<PathBuf>,
+
+    /// Bearer token for authentication, prefer --bearer-token-file (optional)
+    #[arg(
+        long,
+        env = "AGEMON_REMOTE_WRITE_BEARER_TOKEN",
+        hide_env_values = true,
+        global = true
+    )]
+    pub bearer_token: Option<String>,
+
+    /// File containing the bearer token, re-read whenever it changes (optional)
+    #[arg(long, env = "AGEMON_REMOTE_WRITE_BEARER_TOKEN_FILE", global = true)]
+    pub bearer_token_file: Option<PathBuf>,
+
+    /// Extra HTTP header sent with every push, e.g. X-Scope-OrgID=tenant1, can be repeated or
+    /// comma separated
+    #[arg(
+        long = "header",
+        env = "AGEMON_REMOTE_WRITE_HEADERS",
+        value_name = "NAME=VALUE",
+        value_parser = parse_key_value,
+        value_delimiter = ',',
+        global = true
+    )]
+    pub headers: Vec<(String, String)>,
+
+    /// PEM bundle of CAs to verify the endpoint with instead of the built-in roots (optional)
+    #[arg(long, env = "AGEMON_REMOTE_WRITE_TLS_CA_FILE", global = true)]
+    pub tls_ca_file: Option<PathBuf>,
+
+    /// PEM client certificate for mutual TLS, reloaded when it changes (optional)
+    #[arg(long, env = "AGEMON_REMOTE_WRITE_TLS_CERT_FILE", global = true)]
+    pub tls_cert_file: Option<PathBuf>,
+
+    /// PEM private key of the client certificate (optional)
+    #[arg(long, env = "AGEMON_REMOTE_WRITE_TLS_KEY_FILE", global = true)]
+    pub tls_key_file: Option<PathBuf>,
+
+    /// Server name to send (SNI) and verify the certificate against instead of the URL host
+    /// (optional)
+    #[arg(long, env = "AGEMON_REMOTE_WRITE_TLS_SERVER_NAME", global = true)]
+    pub tls_server_name: Option<String>,
+
+    /// Accept any server certificate, only meant for testing
+    #[arg(
+        long,
+        env = "AGEMON_REMOTE_WRITE_TLS_INSECURE_SKIP_VERIFY",
+        global = true
+    )]
+    pub tls_insecure_skip_verify: bool,
+
+    /// Number of top processes to report by CPU and memory (0 to disable)
+    #[arg(
+        short = 't',
+        long,
+        env = "AGEMON_TOP_PROCESSES",
+        default_value_t = 10,
+        global = true
+    )]
+    pub top_processes: usize,
+
+    /// Directory to buffer batches that failed to push, replayed once the endpoint recovers
+    /// (disabled if unset)
+    #[arg(long, env = "AGEMON_BUFFER_DIR", global = true)]
+    pub buffer_dir: Option<PathBuf>,
+
+    /// Maximum size of the on-disk buffer in megabytes, oldest batches are dropped first
+    #[arg(
+        long,
+        env = "AGEMON_BUFFER_MAX_SIZE_MB",
+        default_value_t = 256,
+        global = true
+    )]
+    pub buffer_max_size_mb: u64,
+
+    /// Maximum age of buffered batches in seconds, older batches are dropped
+    #[arg(
+        long,
+        env = "AGEMON_BUFFER_MAX_AGE",
+        default_value_t = 86400,
+        global = true
+    )]
+    pub buffer_max_age: u64,
+
+    /// Maximum number of retries for a failed push (5xx, 429 or connection errors)
+    #[arg(long, env = "AGEMON_MAX_RETRIES", default_value_t = 3, global = true)]
+    pub max_retries: u32,
+
+    /// Initial backoff between push retries in milliseconds, doubled on every retry
+    #[arg(
+        long,
+        env = "AGEMON_RETRY_MIN_BACKOFF_MS",
+        default_value_t = 500,
+        global = true
+    )]
+    pub retry_min_backoff_ms: u64,
+
+    /// Maximum backoff between push retries in milliseconds, a longer Retry-After ends the retries
+    /// and pauses pushes to the endpoint instead
+    #[arg(
+        long,
+        env = "AGEMON_RETRY_MAX_BACKOFF_MS",
+        default_value_t = 10000,
+        global = true
+    )]
+    pub retry_max_backoff_ms: u64,
+
+    /// Seconds to keep pushing queued batches after SIGTERM or SIGINT before giving up
+    #[arg(
+        long,
+        env = "AGEMON_SHUTDOWN_TIMEOUT",
+        default_value_t = 10,
+        global = true
+    )]
+    pub shutdown_timeout: u64,
+
+    /// Interval between sending metric metadata (type, help and unit) in seconds (0 to disable)
+    #[arg(
+        long,
+        env = "AGEMON_METADATA_INTERVAL",
+        default_value_t = 60,
+        global = true
+    )]
+    pub metadata_interval: u64,
+
+    /// Value of the hostname label instead of the system hostname, e.g. inside containers
+    #[arg(long, env = "AGEMON_HOSTNAME", global = true)]
+    pub hostname: Option<String>,
+
+    /// Where the host's /proc is mounted, to monitor the host from a container (not used by the
+    /// sysinfo-based cpu, memory, system, processes and disk_io collectors)
+    #[arg(long, env = "AGEMON_HOST_PROC", default_value = "/proc", global = true)]
+    pub host_proc: PathBuf,
+
+    /// Where the host's /sys is mounted, to monitor the host from a container
+    #[arg(long, env = "AGEMON_HOST_SYS", default_value = "/sys", global = true)]
+    pub host_sys: PathBuf,
+
+    /// Where the host's root filesystem is mounted, for disk space and the hostname (requires
+    /// --host-proc)
+    #[arg(long, env = "AGEMON_HOST_ROOT", default_value = "/", global = true)]
+    pub host_root: PathBuf,
+
+    /// Static label added to every series, e.g. env=prod, can be repeated or comma separated
+    #[arg(
+        long = "label",
+        env = "AGEMON_LABELS",
+        value_name = "NAME=VALUE",
+        value_parser = parse_key_value,
+        value_delimiter = ',',
+        global = true
+    )]
+    pub labels: Vec<(String, String)>,
+
+    /// Address to serve the latest metrics on for Prometheus to scrape, e.g. 0.0.0.0:9101
+    #[arg(short, long, env = "AGEMON_LISTEN_ADDRESS", global = true)]
+    pub listen_address: Option<SocketAddr>,
+
+    /// Only serve metrics for scraping, do not push them via remote write
+    #[arg(long, env = "AGEMON_NO_REMOTE_WRITE", global = true)]
+    pub no_remote_write: bool,
+
+    /// Print the metrics of every cycle to stdout instead of pushing them
+    #[arg(long, env = "AGEMON_DRY_RUN", global = true)]
+    pub dry_run: bool,
+
+    /// Output format of `once` and --dry-run
+    #[arg(long, value_enum, default_value_t = OutputFormat::Prometheus, global = true)]
+    pub format: OutputFormat,
+
+    /// Only run these collectors (comma separated, defaults to all)
+    #[arg(
+        long = "collectors",
+        env = "AGEMON_COLLECTORS",
+        value_name = "COLLECTORS",
+        value_enum,
+        value_delimiter = ',',
+        global = true
+    )]
+    pub enabled_collectors: Vec<CollectorKind>,
+
+    /// Collectors to switch off, can be repeated or comma separated
+    #[arg(
+        long = "disable-collector",
+        env = "AGEMON_DISABLE_COLLECTORS",
+        value_name = "COLLECTOR",
+        value_enum,
+        value_delimiter = ',',
+        global = true
+    )]
+    pub disabled_collectors: Vec<CollectorKind>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Named remote write endpoints, only configurable from the config file
+    #[arg(skip)]
+    pub remote_write: BTreeMap<String, RemoteWriteConfig>,
+
+    /// Relabeling rules applied to every series, only configurable from the config file
+    #[arg(skip)]
+    pub metric_relabel_configs: Vec<RelabelConfig>,
+
+    /// Per-collector settings, only configurable from the config file
+    #[arg(skip)]
+    pub collectors: CollectorsConfig,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Run every collector once, print the metrics to stdout and exit without pushing
+    Once,
+}
+
+impl Args {
+    /// Parse the command line and merge in the config file, if any.
+    pub fn load() -> Result<Self> {
+        Self::from_matches(&Args::command().get_matches())
+    }
+
+    /// Merge the config file into parsed command line arguments and validate the result.
+    pub(crate) fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut args = Args::from_arg_matches(matches).unwrap_or_else(|err| err.exit());
+        for (id, file_flag) in [
+            ("password", "--password-file"),
+            ("bearer_token", "--bearer-token-file"),
+        ] {
+            // Anyone on the host can read the command line of a process
+            if matches.value_source(id) == Some(ValueSource::CommandLine) {
+                warn!(
+                    "--{} is visible in the process list, use {} instead",
+                    id.replace('_', "-"),
+                    file_flag
+                );
+            }
+        }
+        if let Some(path) = &args.config {
+            FileConfig::load(path)?.apply(&mut args, matches);
+        }
+        args.collectors
+            .select(&args.enabled_collectors, &args.disabled_collectors);
+        if args.top_processes == 0 {
+            args.collectors.processes.enabled = false;
+        }
+        if args.no_remote_write && !args.dry_run && args.listen_address.is_none() {
+            return Err(miette!("--no-remote-write requires --listen-address"));
+        }
+        for name in args.remote_write.keys() {
+            // Names end up in directory names and label values
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid {
+                return Err(miette!(
+                    "invalid remote write endpoint name {:?}, only letters, digits, '_' and '-' \
+                     are allowed",
+                    name
+                ));
+            }
+        }
+        if args
+            .hostname
+            .as_ref()
+            .is_some_and(|hostname| hostname.is_empty())
+        {
+            return Err(miette!("--hostname must not be empty"));
+        }
+        for (name, value) in &args.labels {
+            validate_label(name, value)?;
+        }
+        let defaults = HostPaths::default();
+        for (id, path, default) in [
+            ("host_proc", &args.host_proc, &defaults.proc),
+            ("host_sys", &args.host_sys, &defaults.sys),
+            ("host_root", &args.host_root, &defaults.root),
+        ] {
+            // Only check paths that were configured, macOS has no /proc or /sys
+            let configured =
+                matches.value_source(id) != Some(ValueSource::DefaultValue) || path != default;
+            if configured && !path.is_dir() {
+                let flag = format!("--{}", id.replace('_', "-"));
+                return Err(miette!("{} {} is not a directory", flag, path.display()));
+            }
+        }
+        // The host's mount table is only visible through the host's procfs
+        if args.host_paths().is_host_root() && !args.host_paths().is_host_proc() {
+            return Err(miette!("--host-root requires --host-proc"));
+        }
+        for (i, config) in args.metric_relabel_configs.iter().enumerate() {
+            config
+                .validate()
+                .map_err(|err| miette!("invalid metric_relabel_configs[{}]: {}", i, err))?;
+        }
+        Ok(args)
+    }
+
+    /// Where the collectors find the host's filesystems.
+    pub fn host_paths(&self) -> HostPaths {
+        HostPaths {
+            proc: self.host_proc.clone(),
+            sys: self.host_sys.clone(),
+            root: self.host_root.clone(),
+        }
+    }
+
+    /// Whether metrics are pushed via remote write at all.
+    pub fn pushes(&self) -> bool {
+        !self.no_remote_write && !self.dry_run && self.command.is_none()
+    }
+
+    /// The endpoints to push to: the `[remote_write.<name>]` sections of the config file, or a
+    /// single endpoint named `default` built from the top-level options if there are none.
+    pub fn endpoints(&self) -> Vec<(String, RemoteWriteConfig)> {
+        if !self.pushes() {
+            return vec![];
+        }
+        if !self.remote_write.is_empty() {
+            return self
+                .remote_write
+                .iter()
+                .map(|(name, config)| (name.clone(), config.clone()))
+                .collect();
+        }
+        vec![(
+            "default".to_string(),
+            RemoteWriteConfig {
+                url: self.remote_write_url.clone(),
+                username: self.username.clone(),
+                password: self.password.clone(),
+                password_file: self.password_file.clone(),
+                bearer_token: self.bearer_token.clone(),
+                bearer_token_file: self.bearer_token_file.clone(),
+                headers: self.headers.iter().cloned().collect(),
+                tls: TlsConfig {
+                    ca_file: self.tls_ca_file.clone(),
+                    cert_file: self.tls_cert_file.clone(),
+                    key_file: self.tls_key_file.clone(),
+                    server_name: self.tls_server_name.clone(),
+                    insecure_skip_verify: self.tls_insecure_skip_verify,
+                },
+                protocol: self.remote_write_protocol,
+            },
+        )]
+    }
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    let (name, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got {:?}", value))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+/// Check an external label against the Prometheus data model, labels starting with `__` are
+/// reserved and `hostname` is set by agemon itself.
+fn validate_label(name: &str, value: &str) -> Result<()> {
+    if !is_valid_label_name(name) {
+        return Err(miette!(
+            "invalid label name {:?}, it must match [a-zA-Z_][a-zA-Z0-9_]*",
+            name
+        ));
+    }
+    if name.starts_with("__") {
+        return Err(miette!(
+            "label name {:?} is reserved, names starting with __ are for internal use",
+            name
+        ));
+    }
+    if name == "hostname" {
+        return Err(miette!("use --hostname to set the hostname label"));
+    }
+    if value.is_empty() {
+        return Err(miette!("label {} has an empty value", name));
+    }
+    Ok(())
+}
@@ -0,0 +1,355 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use miette::{Result, miette};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
+use tracing::{info, warn};
+
+use crate::{config::RemoteWriteConfig, retry::PushError};
+
+/// Headers sent with every push to an endpoint: the configured extra headers and the
+/// authorization, if any.
+pub(crate) struct RequestHeaders {
+    extra: HeaderMap,
+    auth: Auth,
+}
+
+enum Auth {
+    None,
+    Static(HeaderValue),
+    SecretFile(SecretFile),
+}
+
+impl RequestHeaders {
+    pub fn new(endpoint: &str, config: &RemoteWriteConfig) -> Result<Self> {
+        let mut extra = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| miette!("{}: invalid header name {:?}", endpoint, name))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| miette!("{}: invalid value for header {}", endpoint, name))?;
+            extra.insert(name, value);
+        }
+
+        let basic = config.username.is_some()
+            || config.password.is_some()
+            || config.password_file.is_some();
+        let modes = [
+            basic,
+            config.bearer_token.is_some(),
+            config.bearer_token_file.is_some(),
+            extra.contains_key(AUTHORIZATION),
+        ];
+        if modes.iter().filter(|&&configured| configured).count() > 1 {
+            return Err(miette!(
+                "{}: only one of basic auth, bearer_token, bearer_token_file or an Authorization \
+                 header can be configured",
+                endpoint
+            ));
+        }
+
+        let auth = Auth::new(endpoint, config)?;
+        Ok(RequestHeaders { extra, auth })
+    }
+
+    /// Headers for the next request, re-reading the token file if it changed since the last one.
+    pub fn get(&mut self) -> Result<HeaderMap, PushError> {
+        let mut headers = self.extra.clone();
+        let authorization = match &mut self.auth {
+            Auth::None => None,
+            Auth::Static(value) => Some(value.clone()),
+            Auth::SecretFile(file) => Some(file.authorization()?),
+        };
+        if let Some(authorization) = authorization {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        Ok(headers)
+    }
+}
+
+impl Auth {
+    fn new(endpoint: &str, config: &RemoteWriteConfig) -> Result<Self> {
+        let (scheme, secret, secret_file) = match &config.username {
+            Some(username) => {
+                if config.password.is_some() && config.password_file.is_some() {
+                    return Err(miette!(
+                        "{}: only one of password and password_file can be configured",
+                        endpoint
+                    ));
+                }
+                (
+                    Scheme::Basic(username.clone()),
+                    &config.password,
+                    &config.password_file,
+                )
+            }
+            None if config.password.is_some() || config.password_file.is_some() => {
+                return Err(miette!("{}: basic auth needs a username", endpoint));
+            }
+            None => (
+                Scheme::Bearer,
+                &config.bearer_token,
+                &config.bearer_token_file,
+            ),
+        };
+
+        match (secret, secret_file) {
+            (Some(secret), _) => sensitive(&scheme.header(secret))
+                .map(Auth::Static)
+                .ok_or_else(|| miette!("{}: credentials contain invalid characters", endpoint)),
+            (None, Some(path)) => Ok(Auth::SecretFile(SecretFile {
+                path: path.clone(),
+                scheme,
+                modified: None,
+                authorization: None,
+            })),
+            (None, None) if matches!(scheme, Scheme::Basic(_)) => Err(miette!(
+                "{}: basic auth needs a password or password_file",
+                endpoint
+            )),
+            (None, None) => Ok(Auth::None),
+        }
+    }
+}
+
+enum Scheme {
+    /// Basic auth for this username, the secret is the password
+    Basic(String),
+    Bearer,
+}
+
+impl Scheme {
+    fn header(&self, secret: &str) -> String {
+        match self {
+            Scheme::Basic(username) => {
+                let credentials = STANDARD.encode(format!("{}:{}", username, secret));
+                format!("Basic {}", credentials)
+            }
+            Scheme::Bearer => format!("Bearer {}", secret.trim()),
+        }
+    }
+
+    fn secret_name(&self) -> &'static str {
+        match self {
+            Scheme::Basic(_) => "password",
+            Scheme::Bearer => "bearer token",
+        }
+    }
+}
+
+/// A password or token kept in a file, possibly rotated by another process.
+///
+/// The contents are only ever put into the (sensitive) authorization header, never logged.
+struct SecretFile {
+    path: PathBuf,
+    scheme: Scheme,
+    modified: Option<SystemTime>,
+    authorization: Option<HeaderValue>,
+}
+
+impl SecretFile {
+    /// The current authorization, falling back to the last one read if the file is briefly
+    /// unreadable during a rotation.
+    fn authorization(&mut self) -> Result<HeaderValue, PushError> {
+        if let Err(reason) = self.reload() {
+            match &self.authorization {
+                Some(_) => warn!(
+                    "{}, using the previous {}",
+                    reason,
+                    self.scheme.secret_name()
+                ),
+                None => {
+                    return Err(PushError::Retryable {
+                        reason,
+                        retry_after: None,
+                    });
+                }
+            }
+        }
+        Ok(self.authorization.clone().unwrap())
+    }
+
+    fn reload(&mut self) -> Result<(), String> {
+        let name = self.scheme.secret_name();
+        let error = |err| {
+            format!(
+                "failed to read {} file {}: {}",
+                name,
+                self.path.display(),
+                err
+            )
+        };
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(error)?;
+        if self.authorization.is_some() && self.modified == Some(modified) {
+            return Ok(());
+        }
+
+        let contents = fs::read_to_string(&self.path).map_err(error)?;
+        // Editors and `echo` add a trailing newline that is not part of the secret
+        let secret = contents.trim_end_matches(['\r', '\n']);
+        if secret.is_empty() {
+            return Err(format!("{} file {} is empty", name, self.path.display()));
+        }
+        let authorization = sensitive(&self.scheme.header(secret)).ok_or_else(|| {
+            format!(
+                "{} file {} contains invalid characters",
+                name,
+                self.path.display()
+            )
+        })?;
+        if self.authorization.is_some() {
+            info!("reloaded {} from {}", name, self.path.display());
+        }
+        self.authorization = Some(authorization);
+        self.modified = Some(modified);
+        Ok(())
+    }
+}
+
+/// A header value that is redacted from debug output.
+fn sensitive(value: &str) -> Option<HeaderValue> {
+    let mut value = HeaderValue::from_str(value).ok()?;
+    value.set_sensitive(true);
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, path::Path, time::Duration};
+
+    use super::*;
+
+    fn config(toml: &str) -> RemoteWriteConfig {
+        toml::from_str(&format!("url = \"http://localhost\"\n{}", toml)).unwrap()
+    }
+
+    fn authorization(headers: &mut RequestHeaders) -> String {
+        let headers = headers.get().unwrap();
+        headers[AUTHORIZATION].to_str().unwrap().to_string()
+    }
+
+    /// Write `contents` with a modification time `secs` after the epoch, so changes are seen
+    /// regardless of the filesystem's timestamp resolution.
+    fn write(path: &Path, contents: &str, secs: u64) {
+        fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_conflicting_auth() {
+        for (toml, message) in [
+            (
+                "username = \"u\"\npassword = \"p\"\nbearer_token = \"t\"",
+                "e: only one of basic auth, bearer_token, bearer_token_file or an Authorization \
+                 header can be configured",
+            ),
+            (
+                "bearer_token = \"t\"\nbearer_token_file = \"/t\"",
+                "e: only one of basic auth",
+            ),
+            (
+                "bearer_token = \"t\"\nheaders = { Authorization = \"Bearer x\" }",
+                "e: only one of basic auth",
+            ),
+            (
+                "username = \"u\"\npassword = \"p\"\npassword_file = \"/p\"",
+                "e: only one of password and password_file can be configured",
+            ),
+            (
+                "username = \"u\"",
+                "e: basic auth needs a password or password_file",
+            ),
+            ("password = \"p\"", "e: basic auth needs a username"),
+        ] {
+            let err = RequestHeaders::new("e", &config(toml)).err().unwrap();
+            assert!(err.to_string().starts_with(message), "{toml:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn static_credentials() {
+        let mut headers =
+            RequestHeaders::new("e", &config("username = \"u\"\npassword = \"p\"")).unwrap();
+        assert_eq!(authorization(&mut headers), "Basic dTpw");
+
+        let mut headers = RequestHeaders::new(
+            "e",
+            &config("bearer_token = \"t\"\nheaders = { X-Scope-OrgID = \"a\" }"),
+        )
+        .unwrap();
+        let sent = headers.get().unwrap();
+        assert_eq!(sent[AUTHORIZATION], "Bearer t");
+        assert!(sent[AUTHORIZATION].is_sensitive());
+        assert_eq!(sent["x-scope-orgid"], "a");
+
+        let mut headers = RequestHeaders::new("e", &config("")).unwrap();
+        assert!(headers.get().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rereads_secret_file_when_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        write(&path, "first\n", 1000);
+        let mut headers =
+            RequestHeaders::new("e", &config(&format!("bearer_token_file = {:?}", path))).unwrap();
+        assert_eq!(authorization(&mut headers), "Bearer first");
+
+        // Unchanged modification time, not read again
+        write(&path, "ignored\n", 1000);
+        assert_eq!(authorization(&mut headers), "Bearer first");
+
+        write(&path, "second\r\n", 2000);
+        assert_eq!(authorization(&mut headers), "Bearer second");
+
+        // Briefly missing while being replaced, the previous token is kept
+        fs::remove_file(&path).unwrap();
+        assert_eq!(authorization(&mut headers), "Bearer second");
+        write(&path, "third\n", 3000);
+        assert_eq!(authorization(&mut headers), "Bearer third");
+    }
+
+    #[test]
+    fn password_file_keeps_inner_whitespace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("password");
+        write(&path, " p \n", 1000);
+        let mut headers = RequestHeaders::new(
+            "e",
+            &config(&format!("username = \"u\"\npassword_file = {:?}", path)),
+        )
+        .unwrap();
+        assert_eq!(
+            authorization(&mut headers),
+            format!("Basic {}", STANDARD.encode("u: p "))
+        );
+    }
+
+    #[test]
+    fn unreadable_secret_file_is_retryable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing");
+        let mut headers =
+            RequestHeaders::new("e", &config(&format!("bearer_token_file = {:?}", path))).unwrap();
+        assert!(matches!(
+            headers.get(),
+            Err(PushError::Retryable {
+                retry_after: None,
+                ..
+            })
+        ));
+
+        write(&path, "\n", 1000);
+        let Err(PushError::Retryable { reason, .. }) = headers.get() else {
+            panic!("empty token file accepted");
+        };
+        assert!(reason.ends_with("is empty"), "{reason}");
+    }
+}
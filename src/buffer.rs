@@ -0,0 +1,403 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use miette::{IntoDiagnostic, Result, miette};
+use prometheus_remote_write::{TimeSeries, WriteRequest};
+use prost::Message;
+use tracing::{debug, warn};
+
+const BATCH_EXTENSION: &str = "batch";
+const TMP_EXTENSION: &str = "tmp";
+
+/// Why a buffered batch was discarded without being pushed.
+#[derive(Debug, Clone, Copy)]
+pub enum DropReason {
+    /// Older than the configured maximum age
+    Age,
+    /// Evicted to keep the buffer under the configured maximum size
+    Size,
+    /// Could not be read back from disk
+    Corrupt,
+    /// Permanently rejected by the remote-write endpoint
+    Rejected,
+}
+
+impl DropReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DropReason::Age => "age",
+            DropReason::Size => "size",
+            DropReason::Corrupt => "corrupt",
+            DropReason::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    timestamp: i64,
+    size: u64,
+}
+
+/// Bounded on-disk queue of remote-write batches that failed to push.
+///
+/// Each batch is stored as its own snappy-compressed `WriteRequest` file named after the batch
+/// timestamp, so replay happens in timestamp order and a crash loses at most the batch being
+/// written. Files are written to a temporary name, synced and then renamed into place.
+#[derive(Debug)]
+pub struct DiskBuffer {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_age: Duration,
+    entries: VecDeque<Entry>,
+    total_bytes: u64,
+    next_seq: u64,
+    dropped_age: u64,
+    dropped_size: u64,
+    dropped_corrupt: u64,
+    dropped_rejected: u64,
+}
+
+impl DiskBuffer {
+    /// Open the buffer directory, creating it if needed and picking up batches left by a
+    /// previous run.
+    pub fn open(dir: &Path, max_bytes: u64, max_age: Duration) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .map_err(|err| miette!("failed to create buffer dir {}: {}", dir.display(), err))?;
+
+        let mut entries = vec![];
+        let mut next_seq = 0;
+        for dir_entry in fs::read_dir(dir).into_diagnostic()? {
+            let path = dir_entry.into_diagnostic()?.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some(TMP_EXTENSION) => {
+                    // Leftover from a write interrupted by a crash
+                    debug!("removing incomplete batch {}", path.display());
+                    let _ = fs::remove_file(&path);
+                }
+                Some(BATCH_EXTENSION) => {
+                    let Some((timestamp, seq)) = parse_batch_name(&path) else {
+                        warn!("ignoring unexpected file in buffer dir: {}", path.display());
+                        continue;
+                    };
+                    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                    next_seq = next_seq.max(seq + 1);
+                    entries.push((
+                        seq,
+                        Entry {
+                            path,
+                            timestamp,
+                            size,
+                        },
+                    ));
+                }
+                _ => {}
+            }
+        }
+        entries.sort_by_key(|(seq, entry)| (entry.timestamp, *seq));
+
+        let mut buffer = DiskBuffer {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            max_age,
+            total_bytes: entries.iter().map(|(_, entry)| entry.size).sum(),
+            entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+            next_seq,
+            dropped_age: 0,
+            dropped_size: 0,
+            dropped_corrupt: 0,
+            dropped_rejected: 0,
+        };
+        buffer.enforce_limits();
+
+        Ok(buffer)
+    }
+
+    /// Number of batches waiting to be replayed.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total size of the buffered batches on disk.
+    pub fn size_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Number of batches discarded since startup for the given reason.
+    pub fn dropped_batches(&self, reason: DropReason) -> u64 {
+        match reason {
+            DropReason::Age => self.dropped_age,
+            DropReason::Size => self.dropped_size,
+            DropReason::Corrupt => self.dropped_corrupt,
+            DropReason::Rejected => self.dropped_rejected,
+        }
+    }
+
+    /// Persist a batch that could not be pushed.
+    pub fn enqueue(&mut self, timeseries: Vec<TimeSeries>) -> Result<()> {
+        let timestamp = timeseries
+            .iter()
+            .flat_map(|series| series.samples.iter().map(|sample| sample.timestamp))
+            .min()
+            .unwrap_or_else(now_millis);
+        let data = WriteRequest { timeseries }
+            .encode_compressed()
+            .map_err(|err| miette!("failed to encode batch: {}", err))?;
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let path = self
+            .dir
+            .join(format!("{timestamp:020}-{seq:010}.{BATCH_EXTENSION}"));
+        let tmp_path = path.with_extension(TMP_EXTENSION);
+
+        let write = || -> std::io::Result<()> {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&data)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &path)?;
+            // Make the rename itself durable
+            File::open(&self.dir)?.sync_all()
+        };
+        if let Err(err) = write() {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(miette!("failed to write batch {}: {}", path.display(), err));
+        }
+
+        // Keep entries ordered even if the clock went backwards
+        let index = self
+            .entries
+            .partition_point(|entry| entry.timestamp <= timestamp);
+        self.entries.insert(
+            index,
+            Entry {
+                path,
+                timestamp,
+                size: data.len() as u64,
+            },
+        );
+        self.total_bytes += data.len() as u64;
+        self.enforce_limits();
+
+        Ok(())
+    }
+
+    /// Read the oldest buffered batch, discarding expired or unreadable batches on the way.
+    pub fn front(&mut self) -> Option<Vec<TimeSeries>> {
+        self.enforce_limits();
+        while let Some(entry) = self.entries.front() {
+            match read_batch(&entry.path) {
+                Ok(timeseries) => return Some(timeseries),
+                Err(err) => {
+                    warn!(
+                        "dropping unreadable batch {}: {}",
+                        entry.path.display(),
+                        err
+                    );
+                    self.discard_front(DropReason::Corrupt);
+                }
+            }
+        }
+        None
+    }
+
+    /// Remove the oldest batch after it has been pushed successfully.
+    pub fn remove_front(&mut self) {
+        if let Some(entry) = self.entries.pop_front() {
+            self.total_bytes -= entry.size;
+            if let Err(err) = fs::remove_file(&entry.path) {
+                warn!("failed to remove batch {}: {}", entry.path.display(), err);
+            }
+        }
+    }
+
+    fn enforce_limits(&mut self) {
+        let cutoff = now_millis().saturating_sub(self.max_age.as_millis() as i64);
+        while self
+            .entries
+            .front()
+            .is_some_and(|entry| entry.timestamp < cutoff)
+        {
+            self.discard_front(DropReason::Age);
+        }
+        while self.total_bytes > self.max_bytes && !self.entries.is_empty() {
+            self.discard_front(DropReason::Size);
+        }
+    }
+
+    /// Remove the oldest batch without pushing it.
+    pub fn discard_front(&mut self, reason: DropReason) {
+        if let Some(entry) = self.entries.front() {
+            debug!(
+                "dropping buffered batch {} ({})",
+                entry.path.display(),
+                reason.as_str()
+            );
+        }
+        self.remove_front();
+        match reason {
+            DropReason::Age => self.dropped_age += 1,
+            DropReason::Size => self.dropped_size += 1,
+            DropReason::Corrupt => self.dropped_corrupt += 1,
+            DropReason::Rejected => self.dropped_rejected += 1,
+        }
+    }
+}
+
+fn parse_batch_name(path: &Path) -> Option<(i64, u64)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (timestamp, seq) = stem.split_once('-')?;
+    Some((timestamp.parse().ok()?, seq.parse().ok()?))
+}
+
+fn read_batch(path: &Path) -> Result<Vec<TimeSeries>> {
+    let data = fs::read(path).into_diagnostic()?;
+    let decoded = snap::raw::Decoder::new()
+        .decompress_vec(&data)
+        .into_diagnostic()?;
+    let request = WriteRequest::decode(decoded.as_slice()).into_diagnostic()?;
+    Ok(request.timeseries)
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus_remote_write::{Label, Sample};
+
+    use super::*;
+
+    const MAX_AGE: Duration = Duration::from_secs(3600);
+
+    fn batch(value: f64, timestamp: i64) -> Vec<TimeSeries> {
+        vec![TimeSeries {
+            labels: vec![Label {
+                name: "__name__".to_string(),
+                value: "agemon_test".to_string(),
+            }],
+            samples: vec![Sample { value, timestamp }],
+        }]
+    }
+
+    fn replay(buffer: &mut DiskBuffer) -> Vec<f64> {
+        let mut values = vec![];
+        while let Some(timeseries) = buffer.front() {
+            values.push(timeseries[0].samples[0].value);
+            buffer.remove_front();
+        }
+        values
+    }
+
+    #[test]
+    fn replays_in_timestamp_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut buffer = DiskBuffer::open(dir.path(), u64::MAX, MAX_AGE).unwrap();
+        let now = now_millis();
+        buffer.enqueue(batch(2.0, now - 1000)).unwrap();
+        buffer.enqueue(batch(3.0, now)).unwrap();
+        // Written last but sampled first, e.g. after the clock went backwards
+        buffer.enqueue(batch(1.0, now - 2000)).unwrap();
+        assert_eq!(buffer.len(), 3);
+
+        assert_eq!(replay(&mut buffer), [1.0, 2.0, 3.0]);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.size_bytes(), 0);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn evicts_oldest_over_max_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut buffer = DiskBuffer::open(dir.path(), u64::MAX, MAX_AGE).unwrap();
+        let now = now_millis();
+        buffer.enqueue(batch(1.0, now)).unwrap();
+        let batch_size = buffer.size_bytes();
+
+        let mut buffer = DiskBuffer::open(dir.path(), batch_size * 2, MAX_AGE).unwrap();
+        buffer.enqueue(batch(2.0, now + 1)).unwrap();
+        buffer.enqueue(batch(3.0, now + 2)).unwrap();
+
+        assert_eq!(buffer.len(), 2);
+        assert!(buffer.size_bytes() <= batch_size * 2);
+        assert_eq!(buffer.dropped_batches(DropReason::Size), 1);
+        assert_eq!(replay(&mut buffer), [2.0, 3.0]);
+    }
+
+    #[test]
+    fn drops_expired_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut buffer = DiskBuffer::open(dir.path(), u64::MAX, MAX_AGE).unwrap();
+        let now = now_millis();
+        buffer
+            .enqueue(batch(1.0, now - 2 * MAX_AGE.as_millis() as i64))
+            .unwrap();
+        buffer.enqueue(batch(2.0, now)).unwrap();
+
+        assert_eq!(replay(&mut buffer), [2.0]);
+        assert_eq!(buffer.dropped_batches(DropReason::Age), 1);
+    }
+
+    #[test]
+    fn skips_corrupt_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut buffer = DiskBuffer::open(dir.path(), u64::MAX, MAX_AGE).unwrap();
+        let now = now_millis();
+        for (i, value) in [1.0, 2.0, 3.0].into_iter().enumerate() {
+            buffer.enqueue(batch(value, now + i as i64)).unwrap();
+        }
+        // Truncate the first batch and overwrite the second with garbage
+        let paths: Vec<_> = buffer
+            .entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect();
+        let data = fs::read(&paths[0]).unwrap();
+        fs::write(&paths[0], &data[..data.len() / 2]).unwrap();
+        fs::write(&paths[1], b"not a batch").unwrap();
+
+        assert_eq!(replay(&mut buffer), [3.0]);
+        assert_eq!(buffer.dropped_batches(DropReason::Corrupt), 2);
+        assert!(!paths[0].exists());
+        assert!(!paths[1].exists());
+    }
+
+    #[test]
+    fn survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = now_millis();
+        {
+            let mut buffer = DiskBuffer::open(dir.path(), u64::MAX, MAX_AGE).unwrap();
+            buffer.enqueue(batch(1.0, now)).unwrap();
+            buffer.enqueue(batch(2.0, now)).unwrap();
+        }
+        // Leftovers of a crash mid-write and unrelated files are ignored
+        fs::write(dir.path().join("00000000000000000000-0000000009.tmp"), b"").unwrap();
+        fs::write(dir.path().join("notes.batch"), b"").unwrap();
+
+        let mut buffer = DiskBuffer::open(dir.path(), u64::MAX, MAX_AGE).unwrap();
+        assert_eq!(buffer.len(), 2);
+        // New batches continue the sequence instead of overwriting old ones
+        buffer.enqueue(batch(3.0, now)).unwrap();
+        assert_eq!(replay(&mut buffer), [1.0, 2.0, 3.0]);
+        assert!(
+            !dir.path()
+                .join("00000000000000000000-0000000009.tmp")
+                .exists()
+        );
+    }
+}
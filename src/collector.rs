@@ -0,0 +1,456 @@
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use prometheus_remote_write::{LABEL_NAME, Label, Sample, TimeSeries};
+use sysinfo::System;
+use tracing::{debug, error};
+
+use crate::descriptors::MetricDescriptor;
+
+/// A source of metrics run once per collection cycle.
+///
+/// Implement this for site-specific metrics and add it to a [`Registry`] to have it collected
+/// and pushed alongside the built-in collectors.
+pub trait Collector {
+    /// Short name used in logs.
+    fn name(&self) -> &str;
+
+    /// Type, help and unit of every metric this collector emits, sent as metadata and shown on
+    /// the scrape endpoint.
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        &[]
+    }
+
+    /// Refresh the underlying data before [`Collector::collect`] is called.
+    fn refresh(&mut self) {}
+
+    /// Emit the current values into the sink.
+    fn collect(&mut self, sink: &mut Sink);
+}
+
+/// Collects the series emitted during one cycle, stamping them with the shared timestamp,
+/// hostname label and external labels.
+#[derive(Debug)]
+pub struct Sink {
+    hostname: String,
+    external_labels: Vec<(String, String)>,
+    timestamp: i64,
+    timeseries: Vec<TimeSeries>,
+    /// Start index in `timeseries` of the series of each source, see [`Sink::set_source`]
+    sources: Vec<(String, usize)>,
+    errors: u64,
+}
+
+impl Sink {
+    pub fn new(hostname: &str, timestamp: i64) -> Self {
+        Sink {
+            hostname: hostname.to_string(),
+            external_labels: vec![],
+            timestamp,
+            timeseries: vec![],
+            sources: vec![],
+            errors: 0,
+        }
+    }
+
+    /// Add these labels to every series, unless the series has a label of the same name.
+    pub fn with_external_labels(mut self, labels: Vec<(String, String)>) -> Self {
+        self.external_labels = labels;
+        self
+    }
+
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    /// Timestamp in milliseconds since the Unix epoch shared by all samples in this cycle.
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    pub fn len(&self) -> usize {
+        self.timeseries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timeseries.is_empty()
+    }
+
+    /// Add a sample without labels other than `hostname`.
+    pub fn push(&mut self, metric_name: &str, value: f64) {
+        self.push_with_labels(metric_name, value, &[]);
+    }
+
+    /// Add a sample with additional labels.
+    pub fn push_with_labels(
+        &mut self,
+        metric_name: &str,
+        value: f64,
+        extra_labels: &[(&str, &str)],
+    ) {
+        let mut labels = vec![
+            Label {
+                name: "hostname".to_string(),
+                value: self.hostname.clone(),
+            },
+            Label {
+                name: LABEL_NAME.to_string(),
+                value: metric_name.to_string(),
+            },
+        ];
+        for (k, v) in extra_labels {
+            labels.push(Label {
+                name: k.to_string(),
+                value: v.to_string(),
+            });
+        }
+        for (name, value) in &self.external_labels {
+            if !extra_labels.iter().any(|(k, _)| k == name) {
+                labels.push(Label {
+                    name: name.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        self.timeseries.push(TimeSeries {
+            labels,
+            samples: vec![Sample {
+                value,
+                timestamp: self.timestamp,
+            }],
+        });
+    }
+
+    /// Record a failed read, counted per collector in `agemon_agent_collector_errors_total`.
+    pub fn report_error(&mut self, err: impl fmt::Display) {
+        let source = self
+            .sources
+            .last()
+            .map_or("", |(source, _)| source.as_str());
+        debug!("{}: {}", source, err);
+        self.errors += 1;
+    }
+
+    pub fn into_timeseries(self) -> Vec<TimeSeries> {
+        self.timeseries
+    }
+
+    /// Attribute the series pushed from now on to `source`, usually a collector name.
+    pub(crate) fn set_source(&mut self, source: &str) {
+        self.sources
+            .push((source.to_string(), self.timeseries.len()));
+    }
+
+    /// The series grouped by source, series pushed before any source was set are attributed to
+    /// an empty name.
+    pub(crate) fn into_sources(mut self) -> Vec<(String, Vec<TimeSeries>)> {
+        let mut groups = vec![];
+        while let Some((source, start)) = self.sources.pop() {
+            groups.push((source, self.timeseries.split_off(start)));
+        }
+        if !self.timeseries.is_empty() {
+            groups.push((String::new(), self.timeseries));
+        }
+        groups.reverse();
+        groups
+    }
+}
+
+/// Interval of collectors registered without one, until [`Registry::set_default_interval`].
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The set of collectors to run, in registration order, each on its own schedule.
+pub struct Registry {
+    collectors: Vec<Scheduled>,
+    default_interval: Duration,
+    hostname: Option<String>,
+    external_labels: Vec<(String, String)>,
+}
+
+struct Scheduled {
+    collector: Box<dyn Collector>,
+    interval: Option<Duration>,
+    next_due: Option<Instant>,
+    /// Duration and series count of the last run, `None` before the first one
+    last_run: Option<(Duration, usize)>,
+    errors: u64,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry {
+            collectors: vec![],
+            default_interval: DEFAULT_INTERVAL,
+            hostname: None,
+            external_labels: vec![],
+        }
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a collector run on the default interval.
+    pub fn register(&mut self, collector: impl Collector + 'static) {
+        self.register_with_interval(collector, None);
+    }
+
+    /// Add a collector run every `interval`, or on the default interval if `None`.
+    pub fn register_with_interval(
+        &mut self,
+        collector: impl Collector + 'static,
+        interval: Option<Duration>,
+    ) {
+        self.collectors.push(Scheduled {
+            collector: Box::new(collector),
+            interval: interval.filter(|interval| !interval.is_zero()),
+            next_due: None,
+            last_run: None,
+            errors: 0,
+        });
+    }
+
+    /// Interval of collectors registered without their own.
+    pub fn set_default_interval(&mut self, interval: Duration) {
+        if !interval.is_zero() {
+            self.default_interval = interval;
+        }
+    }
+
+    /// Use this as the `hostname` label instead of the system hostname.
+    pub fn set_hostname(&mut self, hostname: String) {
+        self.hostname = Some(hostname);
+    }
+
+    /// Static labels such as `env` or `region` added to every series.
+    pub fn set_external_labels(&mut self, labels: Vec<(String, String)>) {
+        self.external_labels = labels;
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.collectors
+            .iter()
+            .map(|scheduled| scheduled.collector.name())
+    }
+
+    /// Descriptors of the metrics emitted by all registered collectors.
+    pub fn descriptors(&self) -> impl Iterator<Item = &'static MetricDescriptor> + '_ {
+        self.collectors
+            .iter()
+            .flat_map(|scheduled| scheduled.collector.descriptors())
+    }
+
+    /// Refresh every collector and gather their output into a sink for the current time.
+    pub fn collect(&mut self) -> Sink {
+        let mut sink = self.sink();
+        for scheduled in &mut self.collectors {
+            scheduled.run(&mut sink);
+        }
+        sink
+    }
+
+    /// Run the collectors that are due at `now` into one sink and schedule their next run.
+    ///
+    /// Schedules advance in whole intervals from the first run, so collectors whose intervals
+    /// are multiples of each other keep coming due together and share a push.
+    pub(crate) fn collect_due(&mut self, now: Instant) -> Sink {
+        let mut sink = self.sink();
+        for scheduled in &mut self.collectors {
+            if scheduled.next_due.is_some_and(|due| due > now) {
+                continue;
+            }
+            scheduled.run(&mut sink);
+
+            let interval = scheduled.interval.unwrap_or(self.default_interval);
+            let mut next_due = scheduled.next_due.unwrap_or(now) + interval;
+            // Skip the runs missed while a slow cycle overran
+            while next_due <= now {
+                next_due += interval;
+            }
+            scheduled.next_due = Some(next_due);
+        }
+        sink
+    }
+
+    /// When the next collector is due, `None` if none are registered.
+    pub(crate) fn next_due(&self) -> Option<Instant> {
+        self.collectors
+            .iter()
+            .map(|scheduled| scheduled.next_due.unwrap_or_else(Instant::now))
+            .min()
+    }
+
+    /// Emit how long each collector took, what it emitted and how often it failed.
+    pub(crate) fn collect_metrics(&self, sink: &mut Sink) {
+        for scheduled in &self.collectors {
+            let Some((duration, series)) = scheduled.last_run else {
+                continue;
+            };
+            let labels = [("collector", scheduled.collector.name())];
+
+            // agemon_agent_collector_duration_seconds: Duration of the collector's last run
+            sink.push_with_labels(
+                "agemon_agent_collector_duration_seconds",
+                duration.as_secs_f64(),
+                &labels,
+            );
+
+            // agemon_agent_collector_series: Series emitted by the collector's last run
+            sink.push_with_labels("agemon_agent_collector_series", series as f64, &labels);
+
+            // agemon_agent_collector_errors_total: Failed reads and panics of the collector (counter)
+            sink.push_with_labels(
+                "agemon_agent_collector_errors_total",
+                scheduled.errors as f64,
+                &labels,
+            );
+        }
+    }
+
+    fn sink(&self) -> Sink {
+        let hostname = self
+            .hostname
+            .clone()
+            .or_else(System::host_name)
+            .unwrap_or_else(|| "unknown".to_string());
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        Sink::new(&hostname, timestamp).with_external_labels(self.external_labels.clone())
+    }
+}
+
+impl Scheduled {
+    fn run(&mut self, sink: &mut Sink) {
+        let start = Instant::now();
+        let series = sink.len();
+        let errors = sink.errors;
+
+        sink.set_source(self.collector.name());
+        // A bug in one collector should not take the others down with it
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.collector.refresh();
+            self.collector.collect(sink);
+        }));
+        if result.is_err() {
+            error!("collector {} panicked", self.collector.name());
+            sink.errors += 1;
+        }
+
+        self.last_run = Some((start.elapsed(), sink.len() - series));
+        self.errors += sink.errors - errors;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Counter(&'static str);
+
+    impl Collector for Counter {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn collect(&mut self, sink: &mut Sink) {
+            sink.push("agemon_test", 1.0);
+        }
+    }
+
+    struct Panics;
+
+    impl Collector for Panics {
+        fn name(&self) -> &str {
+            "panics"
+        }
+
+        fn collect(&mut self, _sink: &mut Sink) {
+            panic!("collector bug");
+        }
+    }
+
+    /// Names of the collectors that ran in the cycle at `now`.
+    fn run(registry: &mut Registry, now: Instant) -> Vec<String> {
+        registry
+            .collect_due(now)
+            .into_sources()
+            .into_iter()
+            .map(|(source, _)| source)
+            .collect()
+    }
+
+    #[test]
+    fn collectors_run_on_their_own_interval() {
+        let mut registry = Registry::new();
+        registry.set_hostname("test".to_string());
+        registry.set_default_interval(Duration::from_secs(10));
+        registry.register(Counter("fast"));
+        registry.register_with_interval(Counter("slow"), Some(Duration::from_secs(60)));
+
+        let start = Instant::now();
+        let mut runs = vec![];
+        for tick in 0..13 {
+            runs.push(run(&mut registry, start + Duration::from_secs(tick * 10)));
+        }
+        for (tick, ran) in runs.iter().enumerate() {
+            let expected: &[&str] = if tick % 6 == 0 {
+                &["fast", "slow"]
+            } else {
+                &["fast"]
+            };
+            assert_eq!(ran, expected, "tick {tick}");
+        }
+        assert_eq!(registry.next_due(), Some(start + Duration::from_secs(130)));
+    }
+
+    #[test]
+    fn overrun_skips_missed_runs() {
+        let mut registry = Registry::new();
+        registry.set_hostname("test".to_string());
+        registry.register_with_interval(Counter("slow"), Some(Duration::from_secs(60)));
+
+        let start = Instant::now();
+        assert_eq!(run(&mut registry, start), ["slow"]);
+        // A cycle stalled for 150s runs the collector once, not for every missed interval
+        assert_eq!(
+            run(&mut registry, start + Duration::from_secs(150)),
+            ["slow"]
+        );
+        assert!(run(&mut registry, start + Duration::from_secs(170)).is_empty());
+        assert_eq!(
+            run(&mut registry, start + Duration::from_secs(180)),
+            ["slow"]
+        );
+    }
+
+    #[test]
+    fn panicking_collector_counts_as_error() {
+        let mut registry = Registry::new();
+        registry.set_hostname("test".to_string());
+        registry.register(Panics);
+        registry.register(Counter("ok"));
+
+        let sink = registry.collect();
+        assert_eq!(sink.len(), 1);
+        let mut metrics = Sink::new("test", 0);
+        registry.collect_metrics(&mut metrics);
+        let errors: Vec<_> = metrics
+            .into_timeseries()
+            .into_iter()
+            .filter(|series| {
+                series.labels.iter().any(|label| {
+                    label.name == LABEL_NAME && label.value == "agemon_agent_collector_errors_total"
+                })
+            })
+            .map(|series| series.samples[0].value)
+            .collect();
+        assert_eq!(errors, [1.0, 0.0]);
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tracing::debug;
+
+use crate::{
+    collector::{Collector, Sink},
+    config::{CgroupConfig, HostPaths},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Resource usage per cgroup from the cgroup v2 hierarchy.
+pub struct CgroupCollector {
+    config: CgroupConfig,
+    host: HostPaths,
+    /// Block device names by `major:minor`, io.stat only reports the numbers
+    device_names: HashMap<String, String>,
+}
+
+impl CgroupCollector {
+    pub fn new(config: CgroupConfig, host: HostPaths) -> Self {
+        CgroupCollector {
+            config,
+            host,
+            device_names: HashMap::new(),
+        }
+    }
+
+    fn collect_cgroup(&mut self, dir: &Path, path: &str, sink: &mut Sink) {
+        let labels = [("cgroup", path)];
+
+        if let Some(stat) = read_flat_keyed(&dir.join("cpu.stat")) {
+            for (key, metric_name, scale) in [
+                ("usage_usec", "agemon_cgroup_cpu_usage_seconds_total", 1e-6),
+                ("user_usec", "agemon_cgroup_cpu_user_seconds_total", 1e-6),
+                (
+                    "system_usec",
+                    "agemon_cgroup_cpu_system_seconds_total",
+                    1e-6,
+                ),
+                ("nr_periods", "agemon_cgroup_cpu_periods_total", 1.0),
+                (
+                    "nr_throttled",
+                    "agemon_cgroup_cpu_throttled_periods_total",
+                    1.0,
+                ),
+                (
+                    "throttled_usec",
+                    "agemon_cgroup_cpu_throttled_seconds_total",
+                    1e-6,
+                ),
+            ] {
+                // agemon_cgroup_cpu_*: CPU time and CFS throttling from cpu.stat (counter)
+                if let Some(value) = stat.get(key) {
+                    sink.push_with_labels(metric_name, *value as f64 * scale, &labels);
+                }
+            }
+        }
+
+        // agemon_cgroup_memory_current_bytes: Memory charged to the cgroup and its descendants
+        if let Some(current) = read_value(&dir.join("memory.current")) {
+            sink.push_with_labels(
+                "agemon_cgroup_memory_current_bytes",
+                current as f64,
+                &labels,
+            );
+        }
+
+        // agemon_cgroup_memory_max_bytes: Hard memory limit (omitted when unlimited)
+        if let Some(max) = read_value(&dir.join("memory.max")) {
+            sink.push_with_labels("agemon_cgroup_memory_max_bytes", max as f64, &labels);
+        }
+
+        if let Ok(contents) = fs::read_to_string(dir.join("io.stat")) {
+            for line in contents.lines() {
+                let mut fields = line.split_whitespace();
+                let Some(device) = fields.next() else {
+                    continue;
+                };
+                let device = self.device_name(device);
+                let stat: HashMap<&str, u64> = fields
+                    .filter_map(|field| field.split_once('='))
+                    .filter_map(|(key, value)| Some((key, value.parse().ok()?)))
+                    .collect();
+                for (key, metric_name) in [
+                    ("rbytes", "agemon_cgroup_io_read_bytes_total"),
+                    ("wbytes", "agemon_cgroup_io_written_bytes_total"),
+                    ("rios", "agemon_cgroup_io_reads_total"),
+                    ("wios", "agemon_cgroup_io_writes_total"),
+                ] {
+                    // agemon_cgroup_io_*: Bytes and I/Os per device from io.stat (counter)
+                    if let Some(value) = stat.get(key) {
+                        sink.push_with_labels(
+                            metric_name,
+                            *value as f64,
+                            &[("cgroup", path), ("device", &device)],
+                        );
+                    }
+                }
+            }
+        }
+
+        for resource in ["cpu", "memory", "io"] {
+            let Ok(contents) = fs::read_to_string(dir.join(format!("{resource}.pressure"))) else {
+                continue;
+            };
+            for line in contents.lines() {
+                let mut fields = line.split_whitespace();
+                let Some(kind @ ("some" | "full")) = fields.next() else {
+                    continue;
+                };
+                let total = fields
+                    .filter_map(|field| field.strip_prefix("total="))
+                    .find_map(|value| value.parse::<u64>().ok());
+                // agemon_cgroup_psi_{some,full}_total_us: Time stalled on the resource (counter)
+                if let Some(total) = total {
+                    sink.push_with_labels(
+                        &format!("agemon_cgroup_psi_{kind}_total_us"),
+                        total as f64,
+                        &[("cgroup", path), ("resource", resource)],
+                    );
+                }
+            }
+        }
+    }
+
+    fn device_name(&mut self, device: &str) -> String {
+        self.device_names
+            .entry(device.to_string())
+            .or_insert_with(|| {
+                fs::read_link(self.host.resolve("/sys/dev/block").join(device))
+                    .ok()
+                    .and_then(|target| Some(target.file_name()?.to_string_lossy().into_owned()))
+                    .unwrap_or_else(|| device.to_string())
+            })
+            .clone()
+    }
+}
+
+impl Collector for CgroupCollector {
+    fn name(&self) -> &str {
+        "cgroup"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::CGROUP
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let root = self.host.resolve(&self.config.root);
+        if !root.join("cgroup.controllers").exists() {
+            debug!("no cgroup v2 hierarchy at {}", root.display());
+            return;
+        }
+
+        // Depth-first walk, children of excluded cgroups are still visited
+        let mut pending = vec![(root.clone(), 0)];
+        while let Some((dir, depth)) = pending.pop() {
+            let path = cgroup_path(&root, &dir);
+            if self.config.paths.matches(&path) {
+                self.collect_cgroup(&dir, &path, sink);
+            }
+            if depth >= self.config.max_depth {
+                continue;
+            }
+
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut children: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .map(|entry| entry.path())
+                .collect();
+            // Reverse so children are popped in name order
+            children.sort_unstable_by(|a, b| b.cmp(a));
+            pending.extend(children.into_iter().map(|child| (child, depth + 1)));
+        }
+    }
+}
+
+/// The cgroup path as shown in /proc/<pid>/cgroup, `/` for the root.
+fn cgroup_path(root: &Path, dir: &Path) -> String {
+    let relative = dir.strip_prefix(root).unwrap_or(dir);
+    format!("/{}", relative.display())
+}
+
+/// Parse a file of `key value` lines such as cpu.stat.
+fn read_flat_keyed(path: &Path) -> Option<HashMap<String, u64>> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(
+        contents
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once(' ')?;
+                Some((key.to_string(), value.trim().parse().ok()?))
+            })
+            .collect(),
+    )
+}
+
+/// Read a single-value file, `max` reads as `None`.
+fn read_value(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, os::unix::fs::symlink};
+
+    use prometheus_remote_write::LABEL_NAME;
+
+    use super::*;
+    use crate::config::{Filter, Pattern};
+
+    /// A fake `--host-sys` with a cgroup v2 hierarchy three levels deep.
+    fn hierarchy() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("sys/fs/cgroup");
+        let files: &[(&str, &str)] = &[
+            ("cgroup.controllers", "cpu io memory pids\n"),
+            (
+                "cpu.stat",
+                "usage_usec 2500000\nuser_usec 2000000\nsystem_usec 500000\n",
+            ),
+            (
+                "system.slice/cpu.stat",
+                "usage_usec 1000000\nuser_usec 750000\nsystem_usec 250000\n\
+                 nr_periods 10\nnr_throttled 2\nthrottled_usec 300000\n",
+            ),
+            ("system.slice/memory.current", "4096\n"),
+            ("system.slice/memory.max", "max\n"),
+            (
+                "system.slice/io.stat",
+                "8:0 rbytes=8192 wbytes=4096 rios=2 wios=1 dbytes=0 dios=0\n",
+            ),
+            (
+                "system.slice/memory.pressure",
+                "some avg10=0.00 avg60=0.00 avg300=0.00 total=1500\n\
+                 full avg10=0.00 avg60=0.00 avg300=0.00 total=700\n",
+            ),
+            ("system.slice/ssh.service/memory.current", "1024\n"),
+            ("system.slice/ssh.service/memory.max", "1048576\n"),
+            ("system.slice/ssh.service/session/memory.current", "512\n"),
+            ("user.slice/memory.current", "2048\n"),
+        ];
+        for (path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let block = dir.path().join("sys/dev/block");
+        fs::create_dir_all(&block).unwrap();
+        symlink("../../devices/virtual/block/sda", block.join("8:0")).unwrap();
+        dir
+    }
+
+    /// Collect with the given depth and exclude patterns, returns the series as
+    /// `name{labels} value` without the hostname.
+    fn collect(dir: &Path, max_depth: usize, exclude: &[&str]) -> Vec<String> {
+        let config = CgroupConfig {
+            max_depth,
+            paths: Filter {
+                include: vec![],
+                exclude: exclude
+                    .iter()
+                    .map(|pattern| Pattern::new(pattern).unwrap())
+                    .collect(),
+            },
+            ..CgroupConfig::default()
+        };
+        let host = HostPaths {
+            proc: dir.join("proc"),
+            sys: dir.join("sys"),
+            root: PathBuf::from("/"),
+        };
+        let mut sink = Sink::new("test", 0);
+        CgroupCollector::new(config, host).collect(&mut sink);
+        sink.into_timeseries()
+            .into_iter()
+            .map(|series| {
+                let name = &series
+                    .labels
+                    .iter()
+                    .find(|l| l.name == LABEL_NAME)
+                    .unwrap()
+                    .value;
+                let labels = series
+                    .labels
+                    .iter()
+                    .filter(|l| l.name != LABEL_NAME && l.name != "hostname")
+                    .map(|l| format!("{}={}", l.name, l.value))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("{}{{{}}} {}", name, labels, series.samples[0].value)
+            })
+            .collect()
+    }
+
+    fn cgroups(series: &[String]) -> BTreeSet<&str> {
+        series
+            .iter()
+            .filter_map(|series| series.split("cgroup=").nth(1))
+            .map(|rest| rest.split([',', '}']).next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn reports_stat_files() {
+        let dir = hierarchy();
+        let series = collect(dir.path(), 1, &[]);
+        let system: Vec<_> = series
+            .iter()
+            .filter(|series| {
+                series.contains("cgroup=/system.slice}") || series.contains("cgroup=/system.slice,")
+            })
+            .map(String::as_str)
+            .collect();
+        assert_eq!(
+            system,
+            [
+                "agemon_cgroup_cpu_usage_seconds_total{cgroup=/system.slice} 1",
+                "agemon_cgroup_cpu_user_seconds_total{cgroup=/system.slice} 0.75",
+                "agemon_cgroup_cpu_system_seconds_total{cgroup=/system.slice} 0.25",
+                "agemon_cgroup_cpu_periods_total{cgroup=/system.slice} 10",
+                "agemon_cgroup_cpu_throttled_periods_total{cgroup=/system.slice} 2",
+                "agemon_cgroup_cpu_throttled_seconds_total{cgroup=/system.slice} 0.3",
+                // memory.max is `max`, so there is no limit series
+                "agemon_cgroup_memory_current_bytes{cgroup=/system.slice} 4096",
+                "agemon_cgroup_io_read_bytes_total{cgroup=/system.slice,device=sda} 8192",
+                "agemon_cgroup_io_written_bytes_total{cgroup=/system.slice,device=sda} 4096",
+                "agemon_cgroup_io_reads_total{cgroup=/system.slice,device=sda} 2",
+                "agemon_cgroup_io_writes_total{cgroup=/system.slice,device=sda} 1",
+                "agemon_cgroup_psi_some_total_us{cgroup=/system.slice,resource=memory} 1500",
+                "agemon_cgroup_psi_full_total_us{cgroup=/system.slice,resource=memory} 700",
+            ]
+        );
+    }
+
+    #[test]
+    fn max_depth_limits_the_walk() {
+        let dir = hierarchy();
+        let expected: [&[&str]; 3] = [
+            &["/"],
+            &["/", "/system.slice", "/user.slice"],
+            &[
+                "/",
+                "/system.slice",
+                "/system.slice/ssh.service",
+                "/user.slice",
+            ],
+        ];
+        for (max_depth, expected) in expected.into_iter().enumerate() {
+            let series = collect(dir.path(), max_depth, &[]);
+            assert_eq!(
+                cgroups(&series),
+                expected.iter().copied().collect(),
+                "max_depth {max_depth}"
+            );
+        }
+        let series = collect(dir.path(), 2, &[]);
+        assert!(series.contains(
+            &"agemon_cgroup_memory_max_bytes{cgroup=/system.slice/ssh.service} 1048576".to_string()
+        ));
+    }
+
+    #[test]
+    fn children_of_excluded_cgroups_are_visited() {
+        let dir = hierarchy();
+        let series = collect(dir.path(), 2, &["/system\\.slice", "/user\\..*"]);
+        assert_eq!(
+            cgroups(&series),
+            BTreeSet::from(["/", "/system.slice/ssh.service"])
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use sysinfo::System;
+
+use crate::{
+    collector::{Collector, Sink},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Global and per-core CPU usage.
+pub struct CpuCollector {
+    sys: System,
+}
+
+impl CpuCollector {
+    pub fn new() -> Self {
+        let mut sys = System::new();
+        // Usage is computed between two refreshes, prime the first one
+        sys.refresh_cpu_usage();
+        CpuCollector { sys }
+    }
+}
+
+impl Default for CpuCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for CpuCollector {
+    fn name(&self) -> &str {
+        "cpu"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::CPU
+    }
+
+    fn refresh(&mut self) {
+        self.sys.refresh_cpu_usage();
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let sys = &self.sys;
+
+        // agemon_cpu_usage_percent: Global CPU usage percentage (0-100)
+        sink.push("agemon_cpu_usage_percent", sys.global_cpu_usage() as f64);
+
+        // agemon_cpu_count: Number of logical CPU cores
+        sink.push("agemon_cpu_count", sys.cpus().len() as f64);
+
+        // agemon_cpu_core_usage_percent: Per-core CPU usage percentage
+        for cpu in sys.cpus() {
+            let labels = vec![("cpu", cpu.name())];
+            sink.push_with_labels(
+                "agemon_cpu_core_usage_percent",
+                cpu.cpu_usage() as f64,
+                &labels,
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+#[cfg(not(target_os = "linux"))]
+use sysinfo::Disks;
+
+use crate::{
+    collector::{Collector, Sink},
+    config::{DiskConfig, HostPaths},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Space usage per mounted filesystem.
+pub struct DiskCollector {
+    #[cfg(not(target_os = "linux"))]
+    disks: Disks,
+    #[cfg(target_os = "linux")]
+    host: HostPaths,
+    config: DiskConfig,
+}
+
+/// A mounted filesystem and its space usage.
+struct Mount {
+    mount_point: String,
+    device: String,
+    fs_type: String,
+    total_bytes: u64,
+    available_bytes: u64,
+    /// Total and free inodes, only known on Linux
+    inodes: Option<(u64, u64)>,
+    readonly: bool,
+    removable: bool,
+}
+
+impl DiskCollector {
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    pub fn new(config: DiskConfig, host: HostPaths) -> Self {
+        DiskCollector {
+            #[cfg(not(target_os = "linux"))]
+            disks: Disks::new(),
+            #[cfg(target_os = "linux")]
+            host,
+            config,
+        }
+    }
+
+    /// Filesystems in the host's mount table, skipping the same pseudo and network filesystems
+    /// as sysinfo does on other platforms.
+    #[cfg(target_os = "linux")]
+    fn mounts(&self, sink: &mut Sink) -> Vec<Mount> {
+        use std::{collections::HashSet, path::PathBuf};
+
+        use procfs::{FromRead, MountEntry};
+        use rustix::fs::StatVfsMountFlags;
+
+        // Mount points are looked up under --host-root, so they have to come from the host's
+        // mount namespace, which is init's; otherwise from our own, even with --host-proc
+        let path = if self.host.is_host_root() {
+            self.host.resolve("/proc/1/mounts")
+        } else {
+            PathBuf::from("/proc/self/mounts")
+        };
+        let entries = match Vec::<MountEntry>::from_file(&path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                sink.report_error(format_args!("failed to read {}: {}", path.display(), err));
+                return vec![];
+            }
+        };
+
+        let removable = self.removable_devices();
+        let mut seen = HashSet::new();
+        let mut mounts = vec![];
+        for entry in entries {
+            if is_ignored(&entry)
+                || !seen.insert((
+                    entry.fs_spec.clone(),
+                    entry.fs_file.clone(),
+                    entry.fs_vfstype.clone(),
+                ))
+            {
+                continue;
+            }
+            // Fails for FUSE mounts of other users, among others
+            let Ok(stat) = rustix::fs::statvfs(self.host.resolve(&entry.fs_file)) else {
+                continue;
+            };
+            // A filesystem remounted read-only after I/O errors shows up in either
+            let readonly = entry.fs_mntops.contains_key("ro")
+                || stat.f_flag.contains(StatVfsMountFlags::RDONLY);
+            mounts.push(Mount {
+                removable: removable.contains(&entry.fs_spec),
+                readonly,
+                mount_point: entry.fs_file,
+                device: entry.fs_spec,
+                fs_type: entry.fs_vfstype,
+                total_bytes: stat.f_frsize * stat.f_blocks,
+                available_bytes: stat.f_frsize * stat.f_bavail,
+                inodes: Some((stat.f_files, stat.f_ffree)),
+            });
+        }
+        mounts
+    }
+
+    /// Device paths of USB disks and their partitions.
+    #[cfg(target_os = "linux")]
+    fn removable_devices(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(self.host.resolve("/dev/disk/by-id")) else {
+            return vec![];
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("usb-"))
+            .filter_map(|entry| std::fs::canonicalize(entry.path()).ok())
+            .map(|path| self.host.unresolve(&path).to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn mounts(&self, _sink: &mut Sink) -> Vec<Mount> {
+        self.disks
+            .list()
+            .iter()
+            .map(|disk| Mount {
+                mount_point: disk.mount_point().to_string_lossy().into_owned(),
+                device: disk.name().to_string_lossy().into_owned(),
+                fs_type: disk.file_system().to_string_lossy().into_owned(),
+                total_bytes: disk.total_space(),
+                available_bytes: disk.available_space(),
+                inodes: None,
+                readonly: disk.is_read_only(),
+                removable: disk.is_removable(),
+            })
+            .collect()
+    }
+}
+
+impl Collector for DiskCollector {
+    fn name(&self) -> &str {
+        "disk"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::DISK
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn refresh(&mut self) {
+        self.disks.refresh(true);
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let config = &self.config;
+
+        for mount in self.mounts(sink) {
+            if !config.mount_points.matches(&mount.mount_point)
+                || !config.devices.matches(&mount.device)
+                || !config.fs_types.matches(&mount.fs_type)
+            {
+                continue;
+            }
+
+            let labels = vec![
+                ("mount_point", mount.mount_point.as_str()),
+                ("device", mount.device.as_str()),
+                ("fs_type", mount.fs_type.as_str()),
+            ];
+
+            // agemon_disk_total_bytes: Total disk space in bytes
+            sink.push_with_labels("agemon_disk_total_bytes", mount.total_bytes as f64, &labels);
+
+            // agemon_disk_available_bytes: Available disk space in bytes
+            sink.push_with_labels(
+                "agemon_disk_available_bytes",
+                mount.available_bytes as f64,
+                &labels,
+            );
+
+            // agemon_disk_used_bytes: Used disk space in bytes
+            let used = mount.total_bytes.saturating_sub(mount.available_bytes);
+            sink.push_with_labels("agemon_disk_used_bytes", used as f64, &labels);
+
+            // agemon_disk_usage_ratio: Disk usage ratio (0.0-1.0)
+            let usage_ratio = if mount.total_bytes > 0 {
+                used as f64 / mount.total_bytes as f64
+            } else {
+                0.0
+            };
+            sink.push_with_labels("agemon_disk_usage_ratio", usage_ratio, &labels);
+
+            // agemon_disk_is_removable: Whether the disk is removable (1=yes, 0=no)
+            sink.push_with_labels(
+                "agemon_disk_is_removable",
+                if mount.removable { 1.0 } else { 0.0 },
+                &labels,
+            );
+
+            // agemon_disk_readonly: Whether the filesystem is mounted read-only (1=yes, 0=no)
+            sink.push_with_labels(
+                "agemon_disk_readonly",
+                if mount.readonly { 1.0 } else { 0.0 },
+                &labels,
+            );
+
+            if let Some((inodes_total, inodes_free)) = mount.inodes {
+                // agemon_disk_inodes_total: Total inodes on the filesystem
+                sink.push_with_labels("agemon_disk_inodes_total", inodes_total as f64, &labels);
+
+                // agemon_disk_inodes_free: Free inodes on the filesystem
+                sink.push_with_labels("agemon_disk_inodes_free", inodes_free as f64, &labels);
+            }
+        }
+    }
+}
+
+/// Pseudo filesystems, and network filesystems whose statvfs can hang on a `hard` mount.
+#[cfg(target_os = "linux")]
+fn is_ignored(entry: &procfs::MountEntry) -> bool {
+    let ignored_type = matches!(
+        entry.fs_vfstype.as_str(),
+        "rootfs"
+            | "sysfs"
+            | "proc"
+            | "devtmpfs"
+            | "cgroup"
+            | "cgroup2"
+            | "pstore"
+            | "squashfs"
+            | "rpc_pipefs"
+            | "iso9660"
+            | "devpts"
+            | "hugetlbfs"
+            | "mqueue"
+            | "tmpfs"
+            | "cifs"
+            | "nfs"
+            | "nfs4"
+            | "autofs"
+    );
+    let mount_point = &entry.fs_file;
+    ignored_type
+        || mount_point.starts_with("/sys")
+        || mount_point.starts_with("/proc")
+        || (mount_point.starts_with("/run") && !mount_point.starts_with("/run/media"))
+        || entry.fs_spec.starts_with("sunrpc")
+}
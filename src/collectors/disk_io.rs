@@ -0,0 +1,78 @@
+use sysinfo::ProcessRefreshKind;
+
+use super::ProcessTable;
+use crate::{
+    collector::{Collector, Sink},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Disk I/O aggregated over all processes.
+pub struct DiskIoCollector {
+    processes: ProcessTable,
+}
+
+impl DiskIoCollector {
+    pub fn new() -> Self {
+        Self::with_processes(ProcessTable::new())
+    }
+
+    /// Read processes from a table shared with other collectors.
+    pub fn with_processes(processes: ProcessTable) -> Self {
+        processes.require(ProcessRefreshKind::nothing().with_disk_usage());
+        DiskIoCollector { processes }
+    }
+}
+
+impl Default for DiskIoCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for DiskIoCollector {
+    fn name(&self) -> &str {
+        "disk_io"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::DISK_IO
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let mut total_read_bytes: u64 = 0;
+        let mut total_written_bytes: u64 = 0;
+        let mut read_bytes_per_sec: u64 = 0;
+        let mut written_bytes_per_sec: u64 = 0;
+
+        self.processes.with_refreshed(sink.timestamp(), |sys| {
+            for process in sys.processes().values() {
+                let disk_usage = process.disk_usage();
+                total_read_bytes += disk_usage.total_read_bytes;
+                total_written_bytes += disk_usage.total_written_bytes;
+                read_bytes_per_sec += disk_usage.read_bytes;
+                written_bytes_per_sec += disk_usage.written_bytes;
+            }
+        });
+
+        // agemon_disk_io_read_bytes_total: Total bytes read from disk (counter)
+        sink.push("agemon_disk_io_read_bytes_total", total_read_bytes as f64);
+
+        // agemon_disk_io_written_bytes_total: Total bytes written to disk (counter)
+        sink.push(
+            "agemon_disk_io_written_bytes_total",
+            total_written_bytes as f64,
+        );
+
+        // agemon_disk_io_read_bytes_per_sec: Bytes read per second since last refresh
+        sink.push(
+            "agemon_disk_io_read_bytes_per_sec",
+            read_bytes_per_sec as f64,
+        );
+
+        // agemon_disk_io_written_bytes_per_sec: Bytes written per second since last refresh
+        sink.push(
+            "agemon_disk_io_written_bytes_per_sec",
+            written_bytes_per_sec as f64,
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use procfs::{DiskStats, FromRead};
+
+use crate::{
+    collector::{Collector, Sink},
+    config::{DiskstatsConfig, HostPaths},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Size of the sectors counted in /proc/diskstats, independent of the device's sector size.
+const SECTOR_SIZE: f64 = 512.0;
+
+/// Per-device block I/O counters from /proc/diskstats.
+pub struct DiskstatsCollector {
+    config: DiskstatsConfig,
+    host: HostPaths,
+}
+
+impl DiskstatsCollector {
+    pub fn new(config: DiskstatsConfig, host: HostPaths) -> Self {
+        DiskstatsCollector { config, host }
+    }
+}
+
+impl Collector for DiskstatsCollector {
+    fn name(&self) -> &str {
+        "diskstats"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::DISKSTATS
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let path = self.host.resolve("/proc/diskstats");
+        let stats = match DiskStats::from_file(&path) {
+            Ok(DiskStats(stats)) => stats,
+            Err(err) => {
+                sink.report_error(format_args!("failed to read {}: {}", path.display(), err));
+                return;
+            }
+        };
+
+        for stat in stats {
+            if !self.config.devices.matches(&stat.name) {
+                continue;
+            }
+            let labels = [("device", stat.name.as_str())];
+
+            // agemon_disk_reads_completed_total: Reads completed successfully (counter)
+            sink.push_with_labels(
+                "agemon_disk_reads_completed_total",
+                stat.reads as f64,
+                &labels,
+            );
+
+            // agemon_disk_reads_merged_total: Adjacent reads merged for efficiency (counter)
+            sink.push_with_labels(
+                "agemon_disk_reads_merged_total",
+                stat.merged as f64,
+                &labels,
+            );
+
+            // agemon_disk_read_bytes_total: Bytes read successfully (counter)
+            sink.push_with_labels(
+                "agemon_disk_read_bytes_total",
+                stat.sectors_read as f64 * SECTOR_SIZE,
+                &labels,
+            );
+
+            // agemon_disk_read_time_seconds_total: Time spent reading (counter)
+            sink.push_with_labels(
+                "agemon_disk_read_time_seconds_total",
+                stat.time_reading as f64 / 1000.0,
+                &labels,
+            );
+
+            // agemon_disk_writes_completed_total: Writes completed successfully (counter)
+            sink.push_with_labels(
+                "agemon_disk_writes_completed_total",
+                stat.writes as f64,
+                &labels,
+            );
+
+            // agemon_disk_writes_merged_total: Adjacent writes merged for efficiency (counter)
+            sink.push_with_labels(
+                "agemon_disk_writes_merged_total",
+                stat.writes_merged as f64,
+                &labels,
+            );
+
+            // agemon_disk_written_bytes_total: Bytes written successfully (counter)
+            sink.push_with_labels(
+                "agemon_disk_written_bytes_total",
+                stat.sectors_written as f64 * SECTOR_SIZE,
+                &labels,
+            );
+
+            // agemon_disk_write_time_seconds_total: Time spent writing (counter)
+            sink.push_with_labels(
+                "agemon_disk_write_time_seconds_total",
+                stat.time_writing as f64 / 1000.0,
+                &labels,
+            );
+
+            // agemon_disk_io_now: I/Os currently in progress
+            sink.push_with_labels("agemon_disk_io_now", stat.in_progress as f64, &labels);
+
+            // agemon_disk_io_time_seconds_total: Time spent doing I/Os (counter)
+            sink.push_with_labels(
+                "agemon_disk_io_time_seconds_total",
+                stat.time_in_progress as f64 / 1000.0,
+                &labels,
+            );
+
+            // agemon_disk_io_time_weighted_seconds_total: Weighted I/O time (counter)
+            sink.push_with_labels(
+                "agemon_disk_io_time_weighted_seconds_total",
+                stat.weighted_time_in_progress as f64 / 1000.0,
+                &labels,
+            );
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use sysinfo::{MemoryRefreshKind, System};
+
+use crate::{
+    collector::{Collector, Sink},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Physical memory and swap usage.
+#[derive(Default)]
+pub struct MemoryCollector {
+    sys: System,
+}
+
+impl MemoryCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Collector for MemoryCollector {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::MEMORY
+    }
+
+    fn refresh(&mut self) {
+        self.sys
+            .refresh_memory_specifics(MemoryRefreshKind::everything());
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let sys = &self.sys;
+
+        // agemon_memory_total_bytes: Total physical memory in bytes
+        sink.push("agemon_memory_total_bytes", sys.total_memory() as f64);
+
+        // agemon_memory_used_bytes: Used physical memory in bytes
+        sink.push("agemon_memory_used_bytes", sys.used_memory() as f64);
+
+        // agemon_memory_free_bytes: Free physical memory in bytes
+        sink.push("agemon_memory_free_bytes", sys.free_memory() as f64);
+
+        // agemon_memory_available_bytes: Available physical memory in bytes (includes cached/buffered)
+        sink.push(
+            "agemon_memory_available_bytes",
+            sys.available_memory() as f64,
+        );
+
+        // agemon_memory_usage_ratio: Memory usage ratio (0.0-1.0)
+        let usage_ratio = if sys.total_memory() > 0 {
+            sys.used_memory() as f64 / sys.total_memory() as f64
+        } else {
+            0.0
+        };
+        sink.push("agemon_memory_usage_ratio", usage_ratio);
+
+        // agemon_swap_total_bytes: Total swap space in bytes
+        sink.push("agemon_swap_total_bytes", sys.total_swap() as f64);
+
+        // agemon_swap_used_bytes: Used swap space in bytes
+        sink.push("agemon_swap_used_bytes", sys.used_swap() as f64);
+
+        // agemon_swap_free_bytes: Free swap space in bytes
+        sink.push("agemon_swap_free_bytes", sys.free_swap() as f64);
+
+        // agemon_swap_usage_ratio: Swap usage ratio (0.0-1.0)
+        let swap_ratio = if sys.total_swap() > 0 {
+            sys.used_swap() as f64 / sys.total_swap() as f64
+        } else {
+            0.0
+        };
+        sink.push("agemon_swap_usage_ratio", swap_ratio);
+    }
+}
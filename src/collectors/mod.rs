@@ -0,0 +1,111 @@
+//! Built-in collectors.
+
+#[cfg(target_os = "linux")]
+mod cgroup;
+mod cpu;
+mod disk;
+mod disk_io;
+#[cfg(target_os = "linux")]
+mod diskstats;
+mod memory;
+mod network;
+mod processes;
+#[cfg(target_os = "linux")]
+mod procfs;
+mod system;
+mod temperature;
+
+#[cfg(target_os = "linux")]
+pub use cgroup::CgroupCollector;
+pub use cpu::CpuCollector;
+pub use disk::DiskCollector;
+pub use disk_io::DiskIoCollector;
+#[cfg(target_os = "linux")]
+pub use diskstats::DiskstatsCollector;
+pub use memory::MemoryCollector;
+pub use network::NetworkCollector;
+pub use processes::{ProcessCollector, ProcessTable};
+#[cfg(target_os = "linux")]
+pub use procfs::ProcfsCollector;
+pub use system::SystemCollector;
+pub use temperature::TemperatureCollector;
+
+use crate::{
+    collector::Registry,
+    config::{CollectorKind, CollectorsConfig, HostPaths},
+};
+
+/// Build a registry with every built-in collector enabled in the config, on its configured
+/// interval, reading the host's filesystems from `host`.
+pub fn builtin(config: &CollectorsConfig, top_processes: usize, host: &HostPaths) -> Registry {
+    let mut registry = Registry::new();
+    // Walk the process list once per cycle for both collectors that need it
+    let processes = ProcessTable::new();
+    if config.cpu.enabled {
+        registry.register_with_interval(CpuCollector::new(), config.interval(CollectorKind::Cpu));
+    }
+    if config.memory.enabled {
+        registry.register_with_interval(
+            MemoryCollector::new(),
+            config.interval(CollectorKind::Memory),
+        );
+    }
+    if config.disk.enabled {
+        registry.register_with_interval(
+            DiskCollector::new(config.disk.clone(), host.clone()),
+            config.interval(CollectorKind::Disk),
+        );
+    }
+    if config.disk_io.enabled {
+        registry.register_with_interval(
+            DiskIoCollector::with_processes(processes.clone()),
+            config.interval(CollectorKind::DiskIo),
+        );
+    }
+    #[cfg(target_os = "linux")]
+    if config.diskstats.enabled {
+        registry.register_with_interval(
+            DiskstatsCollector::new(config.diskstats.clone(), host.clone()),
+            config.interval(CollectorKind::Diskstats),
+        );
+    }
+    if config.network.enabled {
+        registry.register_with_interval(
+            NetworkCollector::new(config.network.clone(), host.clone()),
+            config.interval(CollectorKind::Network),
+        );
+    }
+    if config.temperature.enabled {
+        registry.register_with_interval(
+            TemperatureCollector::new(config.temperature.clone()),
+            config.interval(CollectorKind::Temperature),
+        );
+    }
+    if config.system.enabled {
+        registry.register_with_interval(
+            SystemCollector::new(),
+            config.interval(CollectorKind::System),
+        );
+    }
+    #[cfg(target_os = "linux")]
+    if config.procfs.enabled {
+        registry.register_with_interval(
+            ProcfsCollector::new(host.clone()),
+            config.interval(CollectorKind::Procfs),
+        );
+    }
+    #[cfg(target_os = "linux")]
+    if config.cgroup.enabled {
+        registry.register_with_interval(
+            CgroupCollector::new(config.cgroup.clone(), host.clone()),
+            config.interval(CollectorKind::Cgroup),
+        );
+    }
+    if config.processes.enabled && top_processes > 0 {
+        registry.register_with_interval(
+            ProcessCollector::with_processes(top_processes, processes),
+            config.interval(CollectorKind::Processes),
+        );
+    }
+    registry
+}
@@ -0,0 +1,244 @@
+#[cfg(not(target_os = "linux"))]
+use sysinfo::Networks;
+
+use crate::{
+    collector::{Collector, Sink},
+    config::{HostPaths, NetworkConfig},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Traffic, packet and error counters per network interface.
+pub struct NetworkCollector {
+    #[cfg(not(target_os = "linux"))]
+    networks: Networks,
+    #[cfg(target_os = "linux")]
+    host: HostPaths,
+    config: NetworkConfig,
+}
+
+/// Counters of one interface since boot.
+struct Interface {
+    name: String,
+    received_bytes: u64,
+    transmitted_bytes: u64,
+    received_packets: u64,
+    transmitted_packets: u64,
+    received_errors: u64,
+    transmitted_errors: u64,
+}
+
+impl NetworkCollector {
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    pub fn new(config: NetworkConfig, host: HostPaths) -> Self {
+        NetworkCollector {
+            #[cfg(not(target_os = "linux"))]
+            networks: Networks::new(),
+            #[cfg(target_os = "linux")]
+            host,
+            config,
+        }
+    }
+
+    /// Interfaces of the host's network namespace from `/sys/class/net` under `--host-sys`.
+    #[cfg(target_os = "linux")]
+    fn interfaces(&self, sink: &mut Sink) -> Vec<Interface> {
+        let dir = self.host.resolve("/sys/class/net");
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                sink.report_error(format_args!("failed to read {}: {}", dir.display(), err));
+                return vec![];
+            }
+        };
+
+        let mut interfaces = vec![];
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let statistics = entry.path().join("statistics");
+            let counter = |name: &str| {
+                std::fs::read_to_string(statistics.join(name))
+                    .ok()?
+                    .trim()
+                    .parse()
+                    .ok()
+            };
+            let interface = || {
+                Some(Interface {
+                    name: name.clone(),
+                    received_bytes: counter("rx_bytes")?,
+                    transmitted_bytes: counter("tx_bytes")?,
+                    received_packets: counter("rx_packets")?,
+                    transmitted_packets: counter("tx_packets")?,
+                    received_errors: counter("rx_errors")?,
+                    transmitted_errors: counter("tx_errors")?,
+                })
+            };
+            // Reporting 0 would look like a counter reset, e.g. for an interface removed while
+            // reading it
+            match interface() {
+                Some(interface) => interfaces.push(interface),
+                None => tracing::debug!("skipping interface {} with unreadable statistics", name),
+            }
+        }
+        interfaces
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn interfaces(&self, _sink: &mut Sink) -> Vec<Interface> {
+        self.networks
+            .list()
+            .iter()
+            .map(|(name, data)| Interface {
+                name: name.clone(),
+                received_bytes: data.total_received(),
+                transmitted_bytes: data.total_transmitted(),
+                received_packets: data.total_packets_received(),
+                transmitted_packets: data.total_packets_transmitted(),
+                received_errors: data.total_errors_on_received(),
+                transmitted_errors: data.total_errors_on_transmitted(),
+            })
+            .collect()
+    }
+}
+
+impl Collector for NetworkCollector {
+    fn name(&self) -> &str {
+        "network"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::NETWORK
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn refresh(&mut self) {
+        self.networks.refresh(true);
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        for interface in self.interfaces(sink) {
+            if !self.config.interfaces.matches(&interface.name) {
+                continue;
+            }
+
+            let labels = vec![("interface", interface.name.as_str())];
+
+            // agemon_network_received_bytes_total: Total bytes received on interface (counter)
+            sink.push_with_labels(
+                "agemon_network_received_bytes_total",
+                interface.received_bytes as f64,
+                &labels,
+            );
+
+            // agemon_network_transmitted_bytes_total: Total bytes transmitted on interface (counter)
+            sink.push_with_labels(
+                "agemon_network_transmitted_bytes_total",
+                interface.transmitted_bytes as f64,
+                &labels,
+            );
+
+            // agemon_network_received_packets_total: Total packets received on interface (counter)
+            sink.push_with_labels(
+                "agemon_network_received_packets_total",
+                interface.received_packets as f64,
+                &labels,
+            );
+
+            // agemon_network_transmitted_packets_total: Total packets transmitted on interface (counter)
+            sink.push_with_labels(
+                "agemon_network_transmitted_packets_total",
+                interface.transmitted_packets as f64,
+                &labels,
+            );
+
+            // agemon_network_received_errors_total: Total receive errors on interface (counter)
+            sink.push_with_labels(
+                "agemon_network_received_errors_total",
+                interface.received_errors as f64,
+                &labels,
+            );
+
+            // agemon_network_transmitted_errors_total: Total transmit errors on interface (counter)
+            sink.push_with_labels(
+                "agemon_network_transmitted_errors_total",
+                interface.transmitted_errors as f64,
+                &labels,
+            );
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use prometheus_remote_write::LABEL_NAME;
+
+    use super::*;
+
+    #[test]
+    fn skips_interfaces_with_unreadable_statistics() {
+        let dir = tempfile::tempdir().unwrap();
+        let net = dir.path().join("sys/class/net");
+        for (interface, counters) in [
+            (
+                "eth0",
+                &[
+                    "rx_bytes",
+                    "tx_bytes",
+                    "rx_packets",
+                    "tx_packets",
+                    "rx_errors",
+                    "tx_errors",
+                ][..],
+            ),
+            // Removed while its statistics were read
+            ("veth1", &["rx_bytes", "tx_bytes", "rx_packets"][..]),
+        ] {
+            let statistics = net.join(interface).join("statistics");
+            fs::create_dir_all(&statistics).unwrap();
+            for (i, counter) in counters.iter().enumerate() {
+                fs::write(statistics.join(counter), format!("{}\n", i + 1)).unwrap();
+            }
+        }
+        let host = HostPaths {
+            proc: dir.path().join("proc"),
+            sys: dir.path().join("sys"),
+            root: PathBuf::from("/"),
+        };
+
+        let mut sink = Sink::new("test", 0);
+        NetworkCollector::new(NetworkConfig::default(), host).collect(&mut sink);
+        let series: Vec<_> = sink
+            .into_timeseries()
+            .into_iter()
+            .map(|series| {
+                let label = |name| {
+                    let label = series.labels.iter().find(|label| label.name == name);
+                    label.unwrap().value.clone()
+                };
+                (
+                    label(LABEL_NAME),
+                    label("interface"),
+                    series.samples[0].value,
+                )
+            })
+            .collect();
+        assert_eq!(
+            series,
+            [
+                ("agemon_network_received_bytes_total", "eth0", 1.0),
+                ("agemon_network_transmitted_bytes_total", "eth0", 2.0),
+                ("agemon_network_received_packets_total", "eth0", 3.0),
+                ("agemon_network_transmitted_packets_total", "eth0", 4.0),
+                ("agemon_network_received_errors_total", "eth0", 5.0),
+                ("agemon_network_transmitted_errors_total", "eth0", 6.0),
+            ]
+            .map(|(name, interface, value)| (
+                name.to_string(),
+                interface.to_string(),
+                value
+            ))
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::{
+    collector::{Collector, Sink},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// The process list shared by the `processes` and `disk_io` collectors, so every `/proc/<pid>`
+/// is read once per cycle however many of them run.
+#[derive(Clone, Default)]
+pub struct ProcessTable(Arc<Mutex<ProcessTableState>>);
+
+#[derive(Default)]
+struct ProcessTableState {
+    sys: System,
+    kind: ProcessRefreshKind,
+    /// Timestamp of the cycle the processes were last refreshed for
+    refreshed_for: Option<i64>,
+}
+
+impl ProcessTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also refresh what `kind` asks for on every refresh.
+    pub(super) fn require(&self, kind: ProcessRefreshKind) {
+        let mut state = self.0.lock().unwrap();
+        let mut merged = state.kind;
+        if kind.cpu() {
+            merged = merged.with_cpu();
+        }
+        if kind.memory() {
+            merged = merged.with_memory();
+        }
+        if kind.disk_usage() {
+            merged = merged.with_disk_usage();
+        }
+        state.kind = merged;
+        // CPU usage and disk I/O rates are computed between two refreshes, prime the first one
+        state
+            .sys
+            .refresh_processes_specifics(ProcessesToUpdate::All, true, merged);
+    }
+
+    /// Look at the processes as of the cycle at `timestamp`, refreshed by whichever collector
+    /// asks first.
+    pub(super) fn with_refreshed<T>(&self, timestamp: i64, f: impl FnOnce(&System) -> T) -> T {
+        let mut state = self.0.lock().unwrap();
+        if state.refreshed_for != Some(timestamp) {
+            let kind = state.kind;
+            state
+                .sys
+                .refresh_processes_specifics(ProcessesToUpdate::All, true, kind);
+            state.refreshed_for = Some(timestamp);
+        }
+        f(&state.sys)
+    }
+}
+
+/// CPU and memory of the top processes, aggregated by process name.
+pub struct ProcessCollector {
+    processes: ProcessTable,
+    top_n: usize,
+}
+
+impl ProcessCollector {
+    pub fn new(top_n: usize) -> Self {
+        Self::with_processes(top_n, ProcessTable::new())
+    }
+
+    /// Read processes from a table shared with other collectors.
+    pub fn with_processes(top_n: usize, processes: ProcessTable) -> Self {
+        processes.require(ProcessRefreshKind::nothing().with_cpu().with_memory());
+        ProcessCollector { processes, top_n }
+    }
+}
+
+impl Collector for ProcessCollector {
+    fn name(&self) -> &str {
+        "processes"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::PROCESSES
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let top_n = self.top_n;
+
+        // Aggregate CPU and memory by process name
+        let mut cpu_by_name: HashMap<String, f64> = HashMap::new();
+        let mut mem_by_name: HashMap<String, u64> = HashMap::new();
+
+        let count = self.processes.with_refreshed(sink.timestamp(), |sys| {
+            for process in sys.processes().values() {
+                let name = process.name().to_string_lossy().into_owned();
+                *cpu_by_name.entry(name.clone()).or_default() += process.cpu_usage() as f64;
+                // Only count memory for non-thread processes to avoid double-counting RSS
+                // (threads share address space, so each thread reports the same RSS as its parent)
+                if process.thread_kind().is_none() {
+                    *mem_by_name.entry(name).or_default() += process.memory();
+                }
+            }
+            sys.processes().len()
+        });
+
+        // agemon_process_count: Total number of running processes
+        sink.push("agemon_process_count", count as f64);
+
+        // Top N by CPU
+        let mut cpu_sorted: Vec<_> = cpu_by_name.into_iter().collect();
+        cpu_sorted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut other_cpu = 0.0;
+        for (i, (name, cpu)) in cpu_sorted.iter().enumerate() {
+            if i < top_n {
+                sink.push_with_labels(
+                    "agemon_process_cpu_usage_percent",
+                    *cpu,
+                    &[("process", name.as_str())],
+                );
+            } else {
+                other_cpu += cpu;
+            }
+        }
+        if cpu_sorted.len() > top_n {
+            sink.push_with_labels(
+                "agemon_process_cpu_usage_percent",
+                other_cpu,
+                &[("process", "other")],
+            );
+        }
+
+        // Top N by memory
+        let mut mem_sorted: Vec<_> = mem_by_name.into_iter().collect();
+        mem_sorted.sort_by_key(|b| std::cmp::Reverse(b.1));
+
+        let mut other_mem: u64 = 0;
+        for (i, (name, mem)) in mem_sorted.iter().enumerate() {
+            if i < top_n {
+                sink.push_with_labels(
+                    "agemon_process_memory_bytes",
+                    *mem as f64,
+                    &[("process", name.as_str())],
+                );
+            } else {
+                other_mem += mem;
+            }
+        }
+        if mem_sorted.len() > top_n {
+            sink.push_with_labels(
+                "agemon_process_memory_bytes",
+                other_mem as f64,
+                &[("process", "other")],
+            );
+        }
+    }
+}
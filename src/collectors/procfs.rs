@@ -0,0 +1,337 @@
+use std::{fs, path::PathBuf};
+
+use procfs::{
+    CpuPressure, FromRead, FromReadSI, IoPressure, KernelStats, MemoryPressure, ProcError,
+    ProcResult, VmStat, current_system_info,
+    net::{Snmp, TcpNetEntries},
+};
+
+use crate::{
+    collector::{Collector, Sink},
+    config::HostPaths,
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Kernel statistics only available from procfs on Linux: TCP states, file descriptors, CPU
+/// time by mode, PSI, vmstat and SNMP counters.
+#[derive(Default)]
+pub struct ProcfsCollector {
+    host: HostPaths,
+}
+
+impl ProcfsCollector {
+    pub fn new(host: HostPaths) -> Self {
+        ProcfsCollector { host }
+    }
+
+    /// Parse a procfs file, reporting a failure to the sink.
+    fn read<T>(
+        &self,
+        sink: &mut Sink,
+        path: &str,
+        parse: impl FnOnce(PathBuf) -> ProcResult<T>,
+    ) -> Option<T> {
+        let path = self.host.resolve(path);
+        parse(path.clone())
+            .map_err(|err| {
+                sink.report_error(format_args!("failed to read {}: {}", path.display(), err))
+            })
+            .ok()
+    }
+
+    /// Parse a procfs file that not every kernel has.
+    fn try_read<T>(&self, path: &str, parse: impl FnOnce(PathBuf) -> ProcResult<T>) -> Option<T> {
+        parse(self.host.resolve(path)).ok()
+    }
+}
+
+impl Collector for ProcfsCollector {
+    fn name(&self) -> &str {
+        "procfs"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::PROCFS
+    }
+
+    // PSI, entropy and IPv6 are missing on some kernels, so only the files every kernel has
+    // count as errors when they cannot be read
+    fn collect(&mut self, sink: &mut Sink) {
+        let system_info = current_system_info();
+
+        // TCP connection counts by state
+        if let Some(TcpNetEntries(tcp_entries)) = self.read(sink, "/proc/net/tcp", |path| {
+            TcpNetEntries::from_file(path, system_info)
+        }) {
+            let mut established: u64 = 0;
+            let mut listen: u64 = 0;
+            let mut time_wait: u64 = 0;
+            let mut close_wait: u64 = 0;
+            let mut other: u64 = 0;
+
+            for entry in &tcp_entries {
+                match entry.state {
+                    procfs::net::TcpState::Established => established += 1,
+                    procfs::net::TcpState::Listen => listen += 1,
+                    procfs::net::TcpState::TimeWait => time_wait += 1,
+                    procfs::net::TcpState::CloseWait => close_wait += 1,
+                    _ => other += 1,
+                }
+            }
+
+            for (state, count) in [
+                ("established", established),
+                ("listen", listen),
+                ("time_wait", time_wait),
+                ("close_wait", close_wait),
+                ("other", other),
+            ] {
+                sink.push_with_labels("agemon_tcp_connections", count as f64, &[("state", state)]);
+            }
+        }
+
+        // TCP6 connection counts by state
+        if let Some(TcpNetEntries(tcp6_entries)) = self.try_read("/proc/net/tcp6", |path| {
+            TcpNetEntries::from_file(path, system_info)
+        }) {
+            let mut established: u64 = 0;
+            let mut listen: u64 = 0;
+            let mut time_wait: u64 = 0;
+            let mut close_wait: u64 = 0;
+            let mut other: u64 = 0;
+
+            for entry in &tcp6_entries {
+                match entry.state {
+                    procfs::net::TcpState::Established => established += 1,
+                    procfs::net::TcpState::Listen => listen += 1,
+                    procfs::net::TcpState::TimeWait => time_wait += 1,
+                    procfs::net::TcpState::CloseWait => close_wait += 1,
+                    _ => other += 1,
+                }
+            }
+
+            for (state, count) in [
+                ("established", established),
+                ("listen", listen),
+                ("time_wait", time_wait),
+                ("close_wait", close_wait),
+                ("other", other),
+            ] {
+                sink.push_with_labels("agemon_tcp6_connections", count as f64, &[("state", state)]);
+            }
+        }
+
+        // System-wide file descriptor usage
+        if let Some((allocated, max)) = self.read(sink, "/proc/sys/fs/file-nr", file_nr) {
+            sink.push("agemon_file_descriptors_allocated", allocated as f64);
+            sink.push("agemon_file_descriptors_max", max as f64);
+        }
+
+        // Context switches and process forks from /proc/stat
+        if let Some((cpu_ids, kernel_stats)) = self.read(sink, "/proc/stat", |path| {
+            let contents = fs::read_to_string(path)?;
+            let kernel_stats = KernelStats::from_read(contents.as_bytes(), system_info)?;
+            Ok((cpu_ids(&contents), kernel_stats))
+        }) {
+            sink.push("agemon_context_switches_total", kernel_stats.ctxt as f64);
+            sink.push(
+                "agemon_processes_forked_total",
+                kernel_stats.processes as f64,
+            );
+            sink.push(
+                "agemon_procs_running",
+                kernel_stats.procs_running.unwrap_or(0) as f64,
+            );
+            sink.push(
+                "agemon_procs_blocked",
+                kernel_stats.procs_blocked.unwrap_or(0) as f64,
+            );
+
+            // agemon_cpu_seconds_total: Seconds each CPU spent in each mode (counter)
+            // Offline CPUs have no line, so the position in the list is not the CPU number.
+            // Guest time is already part of user and nice time, it is reported separately so
+            // the modes add up.
+            for (cpu, cpu_time) in cpu_ids.iter().zip(&kernel_stats.cpu_time) {
+                for (mode, duration) in [
+                    ("user", Some(cpu_time.user_duration())),
+                    ("nice", Some(cpu_time.nice_duration())),
+                    ("system", Some(cpu_time.system_duration())),
+                    ("idle", Some(cpu_time.idle_duration())),
+                    ("iowait", cpu_time.iowait_duration()),
+                    ("irq", cpu_time.irq_duration()),
+                    ("softirq", cpu_time.softirq_duration()),
+                    ("steal", cpu_time.steal_duration()),
+                ] {
+                    // Older kernels do not report every mode
+                    let Some(duration) = duration else {
+                        continue;
+                    };
+                    sink.push_with_labels(
+                        "agemon_cpu_seconds_total",
+                        duration.as_secs_f64(),
+                        &[("cpu", cpu), ("mode", mode)],
+                    );
+                }
+
+                // agemon_cpu_guest_seconds_total: Seconds each CPU spent running guests (counter)
+                for (mode, duration) in [
+                    ("user", cpu_time.guest_duration()),
+                    ("nice", cpu_time.guest_nice_duration()),
+                ] {
+                    if let Some(duration) = duration {
+                        sink.push_with_labels(
+                            "agemon_cpu_guest_seconds_total",
+                            duration.as_secs_f64(),
+                            &[("cpu", cpu), ("mode", mode)],
+                        );
+                    }
+                }
+            }
+        }
+
+        // PSI (Pressure Stall Information) - cpu, memory, io
+        if let Some(psi) = self.try_read("/proc/pressure/cpu", CpuPressure::from_file) {
+            sink.push("agemon_psi_cpu_some_avg10", psi.some.avg10.into());
+            sink.push("agemon_psi_cpu_some_avg60", psi.some.avg60.into());
+            sink.push("agemon_psi_cpu_some_avg300", psi.some.avg300.into());
+            sink.push("agemon_psi_cpu_some_total_us", psi.some.total as f64);
+        }
+
+        if let Some(psi) = self.try_read("/proc/pressure/memory", MemoryPressure::from_file) {
+            for (prefix, record) in [("some", &psi.some), ("full", &psi.full)] {
+                sink.push(
+                    &format!("agemon_psi_memory_{prefix}_avg10"),
+                    record.avg10.into(),
+                );
+                sink.push(
+                    &format!("agemon_psi_memory_{prefix}_avg60"),
+                    record.avg60.into(),
+                );
+                sink.push(
+                    &format!("agemon_psi_memory_{prefix}_avg300"),
+                    record.avg300.into(),
+                );
+                sink.push(
+                    &format!("agemon_psi_memory_{prefix}_total_us"),
+                    record.total as f64,
+                );
+            }
+        }
+
+        if let Some(psi) = self.try_read("/proc/pressure/io", IoPressure::from_file) {
+            for (prefix, record) in [("some", &psi.some), ("full", &psi.full)] {
+                sink.push(
+                    &format!("agemon_psi_io_{prefix}_avg10"),
+                    record.avg10.into(),
+                );
+                sink.push(
+                    &format!("agemon_psi_io_{prefix}_avg60"),
+                    record.avg60.into(),
+                );
+                sink.push(
+                    &format!("agemon_psi_io_{prefix}_avg300"),
+                    record.avg300.into(),
+                );
+                sink.push(
+                    &format!("agemon_psi_io_{prefix}_total_us"),
+                    record.total as f64,
+                );
+            }
+        }
+
+        // Vmstat - page faults, swap activity, OOM kills
+        if let Some(VmStat(vmstat)) = self.read(sink, "/proc/vmstat", VmStat::from_file) {
+            for (key, metric_name) in [
+                ("pgfault", "agemon_vmstat_pgfault_total"),
+                ("pgmajfault", "agemon_vmstat_pgmajfault_total"),
+                ("pgpgin", "agemon_vmstat_pgpgin_total"),
+                ("pgpgout", "agemon_vmstat_pgpgout_total"),
+                ("pswpin", "agemon_vmstat_pswpin_total"),
+                ("pswpout", "agemon_vmstat_pswpout_total"),
+                ("oom_kill", "agemon_vmstat_oom_kill_total"),
+            ] {
+                if let Some(&value) = vmstat.get(key) {
+                    sink.push(metric_name, value as f64);
+                }
+            }
+        }
+
+        // SNMP TCP/UDP stats - retransmits, segments in/out
+        if let Some(snmp) = self.read(sink, "/proc/net/snmp", Snmp::from_file) {
+            sink.push(
+                "agemon_tcp_retrans_segs_total",
+                snmp.tcp_retrans_segs as f64,
+            );
+            sink.push("agemon_tcp_in_segs_total", snmp.tcp_in_segs as f64);
+            sink.push("agemon_tcp_out_segs_total", snmp.tcp_out_segs as f64);
+            sink.push(
+                "agemon_tcp_active_opens_total",
+                snmp.tcp_active_opens as f64,
+            );
+            sink.push(
+                "agemon_tcp_passive_opens_total",
+                snmp.tcp_passive_opens as f64,
+            );
+            sink.push("agemon_tcp_curr_estab", snmp.tcp_curr_estab as f64);
+            sink.push(
+                "agemon_udp_in_datagrams_total",
+                snmp.udp_in_datagrams as f64,
+            );
+            sink.push(
+                "agemon_udp_out_datagrams_total",
+                snmp.udp_out_datagrams as f64,
+            );
+            sink.push("agemon_udp_in_errors_total", snmp.udp_in_errors as f64);
+        }
+
+        // Entropy available
+        if let Some(entropy) = self.try_read("/proc/sys/kernel/random/entropy_avail", |path| {
+            Ok(fs::read_to_string(path)?.trim().parse::<u16>()?)
+        }) {
+            sink.push("agemon_entropy_available", entropy as f64);
+        }
+    }
+}
+
+/// Names of the per-CPU lines in `/proc/stat`, e.g. `cpu0`, in order.
+fn cpu_ids(stat: &str) -> Vec<String> {
+    stat.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| {
+            name.strip_prefix("cpu")
+                .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Allocated and maximum file handles.
+fn file_nr(path: PathBuf) -> ProcResult<(u64, u64)> {
+    let contents = fs::read_to_string(path)?;
+    let values = contents
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<u64>, _>>()?;
+    match values[..] {
+        [allocated, _, max] => Ok((allocated, max)),
+        _ => Err(ProcError::Other(format!(
+            "unexpected contents {:?}",
+            contents
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_ids_skip_offline_cpus() {
+        let stat = "cpu  10 0 5 100 0 0 0 0 0 0\n\
+                    cpu0 5 0 2 50 0 0 0 0 0 0\n\
+                    cpu2 5 0 3 50 0 0 0 0 0 0\n\
+                    intr 12345 0 0\n\
+                    ctxt 100\n";
+        assert_eq!(cpu_ids(stat), ["cpu0", "cpu2"]);
+    }
+}
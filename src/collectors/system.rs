@@ -0,0 +1,61 @@
+use sysinfo::System;
+
+use crate::{
+    collector::{Collector, Sink},
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Uptime, load averages and OS information.
+#[derive(Default)]
+pub struct SystemCollector;
+
+impl SystemCollector {
+    pub fn new() -> Self {
+        SystemCollector
+    }
+}
+
+impl Collector for SystemCollector {
+    fn name(&self) -> &str {
+        "system"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::SYSTEM
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        // agemon_system_uptime_seconds: System uptime in seconds
+        sink.push("agemon_system_uptime_seconds", System::uptime() as f64);
+
+        // agemon_system_boot_time_seconds: System boot time as Unix timestamp
+        sink.push(
+            "agemon_system_boot_time_seconds",
+            System::boot_time() as f64,
+        );
+
+        // agemon_load_average_1m: 1-minute load average
+        let load_avg = System::load_average();
+        sink.push("agemon_load_average_1m", load_avg.one);
+
+        // agemon_load_average_5m: 5-minute load average
+        sink.push("agemon_load_average_5m", load_avg.five);
+
+        // agemon_load_average_15m: 15-minute load average
+        sink.push("agemon_load_average_15m", load_avg.fifteen);
+
+        // agemon_info: System information (value is always 1, labels contain metadata)
+        let os_name = System::name().unwrap_or_else(|| "unknown".to_string());
+        let os_version = System::os_version().unwrap_or_else(|| "unknown".to_string());
+        let kernel_version = System::kernel_version().unwrap_or_else(|| "unknown".to_string());
+        let arch = System::cpu_arch();
+
+        let info_labels = vec![
+            ("os_name", os_name.as_str()),
+            ("os_version", os_version.as_str()),
+            ("kernel_version", kernel_version.as_str()),
+            ("arch", arch.as_str()),
+        ];
+        sink.push_with_labels("agemon_info", 1.0, &info_labels);
+    }
+}
@@ -0,0 +1,68 @@
+use sysinfo::Components;
+
+use crate::{
+    collector::{Collector, Sink},
+    config::TemperatureConfig,
+    descriptors::{self, MetricDescriptor},
+};
+
+/// Temperature sensors.
+pub struct TemperatureCollector {
+    components: Components,
+    config: TemperatureConfig,
+}
+
+impl TemperatureCollector {
+    pub fn new(config: TemperatureConfig) -> Self {
+        TemperatureCollector {
+            components: Components::new(),
+            config,
+        }
+    }
+}
+
+impl Collector for TemperatureCollector {
+    fn name(&self) -> &str {
+        "temperature"
+    }
+
+    fn descriptors(&self) -> &'static [MetricDescriptor] {
+        descriptors::TEMPERATURE
+    }
+
+    fn refresh(&mut self) {
+        self.components.refresh(true);
+    }
+
+    fn collect(&mut self, sink: &mut Sink) {
+        let config = &self.config;
+
+        for component in self.components.list() {
+            let sensor = component.label();
+            if !config.sensors.matches(sensor) {
+                continue;
+            }
+
+            let labels = vec![("sensor", sensor)];
+
+            // agemon_temperature_celsius: Current temperature of the sensor
+            if let Some(temp) = component.temperature() {
+                sink.push_with_labels("agemon_temperature_celsius", temp as f64, &labels);
+            }
+
+            // agemon_temperature_max_celsius: Maximum observed temperature of the sensor
+            if let Some(max) = component.max() {
+                sink.push_with_labels("agemon_temperature_max_celsius", max as f64, &labels);
+            }
+
+            // agemon_temperature_critical_celsius: Critical threshold temperature (only if available)
+            if let Some(critical) = component.critical() {
+                sink.push_with_labels(
+                    "agemon_temperature_critical_celsius",
+                    critical as f64,
+                    &labels,
+                );
+            }
+        }
+    }
+}
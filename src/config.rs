@@ -0,0 +1,787 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{ArgMatches, ValueEnum, parser::ValueSource};
+use miette::{LabeledSpan, NamedSource, Result, miette};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Deserializer};
+
+use crate::{args::Args, exporter::OutputFormat};
+
+/// Contents of the `--config` TOML file.
+///
+/// Top-level keys mirror the command line flags (with underscores instead of dashes), collector
+/// specific settings live in `[collectors.<name>]` sections.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    interval: Option<u64>,
+    remote_write_url: Option<String>,
+    remote_write_protocol: Option<RemoteWriteProtocol>,
+    username: Option<String>,
+    password: Option<String>,
+    password_file: Option<PathBuf>,
+    bearer_token: Option<String>,
+    bearer_token_file: Option<PathBuf>,
+    headers: Option<BTreeMap<String, String>>,
+    tls_ca_file: Option<PathBuf>,
+    tls_cert_file: Option<PathBuf>,
+    tls_key_file: Option<PathBuf>,
+    tls_server_name: Option<String>,
+    tls_insecure_skip_verify: Option<bool>,
+    top_processes: Option<usize>,
+    buffer_dir: Option<PathBuf>,
+    buffer_max_size_mb: Option<u64>,
+    buffer_max_age: Option<u64>,
+    max_retries: Option<u32>,
+    retry_min_backoff_ms: Option<u64>,
+    retry_max_backoff_ms: Option<u64>,
+    shutdown_timeout: Option<u64>,
+    metadata_interval: Option<u64>,
+    hostname: Option<String>,
+    host_proc: Option<PathBuf>,
+    host_sys: Option<PathBuf>,
+    host_root: Option<PathBuf>,
+    labels: Option<BTreeMap<String, String>>,
+    listen_address: Option<SocketAddr>,
+    no_remote_write: Option<bool>,
+    dry_run: Option<bool>,
+    format: Option<OutputFormat>,
+    #[serde(default)]
+    remote_write: BTreeMap<String, RemoteWriteConfig>,
+    #[serde(default)]
+    metric_relabel_configs: Vec<RelabelConfig>,
+    #[serde(default)]
+    collectors: CollectorsConfig,
+}
+
+impl FileConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| miette!("failed to read config file {}: {}", path.display(), err))?;
+
+        toml::from_str(&contents).map_err(|err| {
+            let report = match err.span() {
+                Some(span) => miette!(
+                    labels = vec![LabeledSpan::at(span, err.message().to_string())],
+                    "invalid config file {}",
+                    path.display()
+                ),
+                None => miette!("invalid config file {}: {}", path.display(), err.message()),
+            };
+            report.with_source_code(NamedSource::new(
+                path.display().to_string(),
+                contents.clone(),
+            ))
+        })
+    }
+
+    /// Fill in every setting that was not given on the command line or through the environment.
+    pub fn apply(self, args: &mut Args, matches: &ArgMatches) {
+        let explicit = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field
+                        && !explicit(stringify!($field))
+                    {
+                        args.$field = value.into();
+                    }
+                )*
+            };
+        }
+
+        merge!(
+            interval,
+            remote_write_url,
+            remote_write_protocol,
+            username,
+            password,
+            password_file,
+            bearer_token,
+            bearer_token_file,
+            tls_ca_file,
+            tls_cert_file,
+            tls_key_file,
+            tls_server_name,
+            tls_insecure_skip_verify,
+            top_processes,
+            buffer_dir,
+            buffer_max_size_mb,
+            buffer_max_age,
+            max_retries,
+            retry_min_backoff_ms,
+            retry_max_backoff_ms,
+            shutdown_timeout,
+            metadata_interval,
+            hostname,
+            host_proc,
+            host_sys,
+            host_root,
+            listen_address,
+            no_remote_write,
+            dry_run,
+            format,
+        );
+        if let Some(headers) = self.headers
+            && !explicit("headers")
+        {
+            args.headers = headers.into_iter().collect();
+        }
+        if let Some(labels) = self.labels
+            && !explicit("labels")
+        {
+            args.labels = labels.into_iter().collect();
+        }
+        args.remote_write = self.remote_write;
+        args.metric_relabel_configs = self.metric_relabel_configs;
+        args.collectors = self.collectors;
+    }
+}
+
+/// A named remote write endpoint from a `[remote_write.<name>]` section.
+///
+/// Each endpoint gets its own queue, retries and buffer, so a slow endpoint does not hold back
+/// the others.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteWriteConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Re-read whenever it changes, like `bearer_token_file`
+    pub password_file: Option<PathBuf>,
+    pub bearer_token: Option<String>,
+    /// Re-read whenever it changes, for tokens rotated by an external process
+    pub bearer_token_file: Option<PathBuf>,
+    /// Extra HTTP headers sent with every push, e.g. `X-Scope-OrgID`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub protocol: RemoteWriteProtocol,
+}
+
+/// TLS settings of a remote write endpoint from a `[remote_write.<name>.tls]` section.
+///
+/// Certificate files are checked before every push and reloaded when they change.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM bundle of CAs to trust instead of the built-in roots
+    pub ca_file: Option<PathBuf>,
+    /// PEM client certificate chain for mutual TLS
+    pub cert_file: Option<PathBuf>,
+    /// PEM private key of the client certificate
+    pub key_file: Option<PathBuf>,
+    /// Server name to send (SNI) and verify the certificate against instead of the URL host
+    pub server_name: Option<String>,
+    /// Accept any server certificate, only meant for testing
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+/// Where the host's procfs, sysfs and root filesystem are mounted, for monitoring the host from
+/// inside a container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostPaths {
+    pub proc: PathBuf,
+    pub sys: PathBuf,
+    pub root: PathBuf,
+}
+
+impl Default for HostPaths {
+    fn default() -> Self {
+        HostPaths {
+            proc: PathBuf::from("/proc"),
+            sys: PathBuf::from("/sys"),
+            root: PathBuf::from("/"),
+        }
+    }
+}
+
+impl HostPaths {
+    /// Where the host path `path` is mounted, e.g. `/host/proc/stat` for `/proc/stat` with
+    /// `--host-proc /host/proc`.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        if let Ok(rest) = path.strip_prefix("/proc") {
+            self.proc.join(rest)
+        } else if let Ok(rest) = path.strip_prefix("/sys") {
+            self.sys.join(rest)
+        } else {
+            self.root.join(path.strip_prefix("/").unwrap_or(path))
+        }
+    }
+
+    /// The host path of `path` found under one of the mounts, the inverse of
+    /// [`HostPaths::resolve`] for paths below the root mount.
+    pub fn unresolve(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(rest) => Path::new("/").join(rest),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Whether procfs is mounted from elsewhere, i.e. the agent runs in a container.
+    pub fn is_host_proc(&self) -> bool {
+        self.proc != Path::new("/proc")
+    }
+
+    /// Whether the root filesystem is mounted from elsewhere, i.e. mount points have to be
+    /// looked up under `--host-root`.
+    pub fn is_host_root(&self) -> bool {
+        self.root != Path::new("/")
+    }
+
+    /// The host's hostname from `/etc/hostname` under `--host-root`, `None` without it.
+    pub fn hostname(&self) -> Option<String> {
+        if !self.is_host_root() {
+            return None;
+        }
+        let hostname = fs::read_to_string(self.resolve("/etc/hostname")).ok()?;
+        Some(hostname.trim().to_string()).filter(|hostname| !hostname.is_empty())
+    }
+}
+
+/// Remote write protocol version used for pushes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteWriteProtocol {
+    /// Remote write 1.0, supported by every receiver
+    #[default]
+    V1,
+    /// Remote write 2.0, interns label strings and sends metadata with every series
+    V2,
+}
+
+/// A Prometheus-style relabeling rule from a `[[metric_relabel_configs]]` entry, applied in
+/// order to every series before it is pushed or served.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelabelConfig {
+    /// Labels whose values are joined with `separator` and matched against `regex`
+    pub source_labels: Vec<String>,
+    pub separator: String,
+    pub regex: Pattern,
+    /// Label written by `replace` (may reference capture groups) and `hashmod`
+    pub target_label: Option<String>,
+    /// Value written by `replace`, `$1` and `${name}` refer to capture groups
+    pub replacement: String,
+    /// Number of buckets for `hashmod`
+    pub modulus: Option<u64>,
+    pub action: RelabelAction,
+}
+
+impl Default for RelabelConfig {
+    fn default() -> Self {
+        RelabelConfig {
+            source_labels: vec![],
+            separator: ";".to_string(),
+            regex: Pattern::new("(.*)").unwrap(),
+            target_label: None,
+            replacement: "$1".to_string(),
+            modulus: None,
+            action: RelabelAction::Replace,
+        }
+    }
+}
+
+impl RelabelConfig {
+    /// Check that the rule has the fields its action needs.
+    pub fn validate(&self) -> Result<(), String> {
+        let target_label = match self.action {
+            RelabelAction::Replace | RelabelAction::Hashmod => self
+                .target_label
+                .as_deref()
+                .ok_or_else(|| format!("{} requires target_label", self.action.as_str()))?,
+            _ => return Ok(()),
+        };
+        // Templates are only known to be valid once expanded
+        if !target_label.contains('$') && !is_valid_label_name(target_label) {
+            return Err(format!("invalid target_label {:?}", target_label));
+        }
+        if self.action == RelabelAction::Hashmod && self.modulus.unwrap_or(0) == 0 {
+            return Err("hashmod requires a modulus greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelabelAction {
+    /// Set `target_label` to `replacement` when the source labels match
+    #[default]
+    Replace,
+    /// Drop series whose source labels do not match
+    Keep,
+    /// Drop series whose source labels match
+    Drop,
+    /// Set `target_label` to a hash of the source labels modulo `modulus`
+    Hashmod,
+    /// Remove labels whose name matches
+    Labeldrop,
+    /// Remove labels whose name does not match
+    Labelkeep,
+}
+
+impl RelabelAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RelabelAction::Replace => "replace",
+            RelabelAction::Keep => "keep",
+            RelabelAction::Drop => "drop",
+            RelabelAction::Hashmod => "hashmod",
+            RelabelAction::Labeldrop => "labeldrop",
+            RelabelAction::Labelkeep => "labelkeep",
+        }
+    }
+}
+
+/// Whether `name` is a valid Prometheus label name, `[a-zA-Z_][a-zA-Z0-9_]*`.
+pub fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A collector that can be switched on or off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum CollectorKind {
+    Cpu,
+    Memory,
+    Disk,
+    DiskIo,
+    Diskstats,
+    Network,
+    Temperature,
+    System,
+    Procfs,
+    Cgroup,
+    Processes,
+}
+
+/// Per-collector settings from the `[collectors.*]` sections.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectorsConfig {
+    pub cpu: CollectorConfig,
+    pub memory: CollectorConfig,
+    pub disk: DiskConfig,
+    pub disk_io: CollectorConfig,
+    pub diskstats: DiskstatsConfig,
+    pub network: NetworkConfig,
+    pub temperature: TemperatureConfig,
+    pub system: CollectorConfig,
+    pub procfs: CollectorConfig,
+    pub cgroup: CgroupConfig,
+    pub processes: CollectorConfig,
+}
+
+impl CollectorsConfig {
+    pub fn set_enabled(&mut self, kind: CollectorKind, enabled: bool) {
+        let flag = match kind {
+            CollectorKind::Cpu => &mut self.cpu.enabled,
+            CollectorKind::Memory => &mut self.memory.enabled,
+            CollectorKind::Disk => &mut self.disk.enabled,
+            CollectorKind::DiskIo => &mut self.disk_io.enabled,
+            CollectorKind::Diskstats => &mut self.diskstats.enabled,
+            CollectorKind::Network => &mut self.network.enabled,
+            CollectorKind::Temperature => &mut self.temperature.enabled,
+            CollectorKind::System => &mut self.system.enabled,
+            CollectorKind::Procfs => &mut self.procfs.enabled,
+            CollectorKind::Cgroup => &mut self.cgroup.enabled,
+            CollectorKind::Processes => &mut self.processes.enabled,
+        };
+        *flag = enabled;
+    }
+
+    /// The configured collection interval of a collector, if it differs from the global one.
+    pub fn interval(&self, kind: CollectorKind) -> Option<Duration> {
+        let interval = match kind {
+            CollectorKind::Cpu => self.cpu.interval,
+            CollectorKind::Memory => self.memory.interval,
+            CollectorKind::Disk => self.disk.interval,
+            CollectorKind::DiskIo => self.disk_io.interval,
+            CollectorKind::Diskstats => self.diskstats.interval,
+            CollectorKind::Network => self.network.interval,
+            CollectorKind::Temperature => self.temperature.interval,
+            CollectorKind::System => self.system.interval,
+            CollectorKind::Procfs => self.procfs.interval,
+            CollectorKind::Cgroup => self.cgroup.interval,
+            CollectorKind::Processes => self.processes.interval,
+        };
+        interval.map(Duration::from_secs)
+    }
+
+    /// Apply the `--collectors` and `--disable-collector` selection on top of the config file.
+    pub fn select(&mut self, only: &[CollectorKind], disabled: &[CollectorKind]) {
+        if !only.is_empty() {
+            for kind in CollectorKind::value_variants() {
+                self.set_enabled(*kind, only.contains(kind));
+            }
+        }
+        for kind in disabled {
+            self.set_enabled(*kind, false);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectorConfig {
+    pub enabled: bool,
+    /// Seconds between collections, defaults to the global interval
+    pub interval: Option<u64>,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        CollectorConfig {
+            enabled: true,
+            interval: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiskConfig {
+    pub enabled: bool,
+    /// Seconds between collections, defaults to the global interval
+    pub interval: Option<u64>,
+    pub mount_points: Filter,
+    pub devices: Filter,
+    pub fs_types: Filter,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        DiskConfig {
+            enabled: true,
+            interval: None,
+            mount_points: Filter::default(),
+            devices: Filter::default(),
+            fs_types: Filter::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiskstatsConfig {
+    pub enabled: bool,
+    /// Seconds between collections, defaults to the global interval
+    pub interval: Option<u64>,
+    pub devices: Filter,
+}
+
+impl Default for DiskstatsConfig {
+    fn default() -> Self {
+        DiskstatsConfig {
+            enabled: true,
+            interval: None,
+            // Loop, RAM and floppy devices only add noise
+            devices: Filter {
+                include: vec![],
+                exclude: vec![Pattern::new(r"(loop|ram|fd)\d+").unwrap()],
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub enabled: bool,
+    /// Seconds between collections, defaults to the global interval
+    pub interval: Option<u64>,
+    pub interfaces: Filter,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            enabled: true,
+            interval: None,
+            interfaces: Filter::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemperatureConfig {
+    pub enabled: bool,
+    /// Seconds between collections, defaults to the global interval
+    pub interval: Option<u64>,
+    pub sensors: Filter,
+}
+
+impl Default for TemperatureConfig {
+    fn default() -> Self {
+        TemperatureConfig {
+            enabled: true,
+            interval: None,
+            sensors: Filter::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CgroupConfig {
+    pub enabled: bool,
+    /// Seconds between collections, defaults to the global interval
+    pub interval: Option<u64>,
+    /// Mount point of the cgroup v2 hierarchy on the host, found under `--host-sys`
+    pub root: PathBuf,
+    /// How many levels below the root to report, 0 only reports the root cgroup
+    pub max_depth: usize,
+    pub paths: Filter,
+}
+
+impl Default for CgroupConfig {
+    fn default() -> Self {
+        CgroupConfig {
+            enabled: true,
+            interval: None,
+            root: PathBuf::from("/sys/fs/cgroup"),
+            // Slices and the services directly below them
+            max_depth: 2,
+            paths: Filter::default(),
+        }
+    }
+}
+
+/// Include and exclude lists of regular expressions, each matched against the whole value.
+///
+/// An empty include list includes everything, excludes are applied after includes.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filter {
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+}
+
+impl Filter {
+    pub fn matches(&self, value: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.is_match(value)))
+            && !self.exclude.iter().any(|p| p.is_match(value))
+    }
+}
+
+/// A regular expression anchored at both ends.
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        // Validate the pattern as written so errors point at the user's input
+        Regex::new(pattern)?;
+        Regex::new(&format!("^(?:{pattern})$")).map(Pattern)
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+
+    pub fn captures<'h>(&self, value: &'h str) -> Option<Captures<'h>> {
+        self.0.captures(value)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Pattern::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::args::Command;
+
+    /// Parse `flags` with a config file containing `toml`, like `agemon --config <file> <flags>`.
+    fn load(toml: &str, flags: &[&str]) -> Result<Args> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agemon.toml");
+        fs::write(&path, toml).unwrap();
+        let argv = ["agemon", "--config", path.to_str().unwrap()]
+            .into_iter()
+            .chain(flags.iter().copied());
+        let matches = Args::command().try_get_matches_from(argv).unwrap();
+        Args::from_matches(&matches)
+    }
+
+    #[test]
+    fn command_line_over_file_over_default() {
+        let args = load(
+            r#"
+            interval = 30
+            max_retries = 7
+            hostname = "from-file"
+
+            [labels]
+            env = "file"
+            "#,
+            &["--interval", "5", "--label", "env=cli"],
+        )
+        .unwrap();
+        // Command line wins over the file
+        assert_eq!(args.interval, 5);
+        assert_eq!(args.labels, [("env".to_string(), "cli".to_string())]);
+        // The file wins over defaults
+        assert_eq!(args.max_retries, 7);
+        assert_eq!(args.hostname.as_deref(), Some("from-file"));
+        // Defaults fill in the rest
+        assert_eq!(args.metadata_interval, 60);
+        assert_eq!(args.host_paths(), HostPaths::default());
+    }
+
+    #[test]
+    fn options_after_subcommand() {
+        let args = load(
+            "format = \"json\"",
+            &["once", "--hostname", "x", "--interval", "5"],
+        )
+        .unwrap();
+        assert_eq!(args.command, Some(Command::Once));
+        assert_eq!(args.hostname.as_deref(), Some("x"));
+        assert_eq!(args.interval, 5);
+        assert_eq!(args.format, OutputFormat::Json);
+
+        let args = load("dry_run = true", &["once", "--format", "prometheus"]).unwrap();
+        assert!(args.dry_run);
+        assert_eq!(args.format, OutputFormat::Prometheus);
+    }
+
+    #[test]
+    fn default_host_paths_are_not_checked() {
+        let args = load("", &[]).unwrap();
+        assert_eq!(args.host_paths(), HostPaths::default());
+        // Configured paths still have to exist
+        let err = load("host_sys = \"/nonexistent\"", &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--host-sys /nonexistent is not a directory"
+        );
+    }
+
+    #[test]
+    fn flag_equal_to_default_still_wins() {
+        let args = load("interval = 30", &["--interval", "15"]).unwrap();
+        assert_eq!(args.interval, 15);
+    }
+
+    #[test]
+    fn collector_sections_and_selection() {
+        let args = load(
+            r#"
+            [collectors.disk]
+            interval = 300
+            mount_points.exclude = ["/boot.*"]
+
+            [collectors.temperature]
+            enabled = false
+            "#,
+            &["--disable-collector", "cgroup"],
+        )
+        .unwrap();
+        let collectors = &args.collectors;
+        assert_eq!(
+            collectors.interval(CollectorKind::Disk),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(collectors.interval(CollectorKind::Cpu), None);
+        assert!(!collectors.disk.mount_points.matches("/boot/efi"));
+        assert!(collectors.disk.mount_points.matches("/"));
+        assert!(!collectors.temperature.enabled);
+        assert!(!collectors.cgroup.enabled);
+        assert!(collectors.cpu.enabled);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        for toml in [
+            "intervall = 30",
+            "[collectors.disk]\nmountpoints = []",
+            "[collectors.gpu]\nenabled = true",
+            "[remote_write.main]\nurl = \"http://localhost\"\nuser = \"x\"",
+            "[[metric_relabel_configs]]\nsource_label = [\"job\"]",
+        ] {
+            let err = load(toml, &[]).unwrap_err();
+            assert!(
+                err.to_string().starts_with("invalid config file"),
+                "{toml:?}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for toml in [
+            "interval = \"fast\"",
+            "remote_write_protocol = \"v3\"",
+            "listen_address = \"localhost\"",
+            "[collectors.network]\ninterfaces.include = [\"(\"]",
+            "[[metric_relabel_configs]]\naction = \"frobnicate\"",
+        ] {
+            let err = load(toml, &[]).unwrap_err();
+            assert!(
+                err.to_string().starts_with("invalid config file"),
+                "{toml:?}: {err}"
+            );
+        }
+
+        for (toml, message) in [
+            (
+                "[labels]\n__meta = \"x\"",
+                "label name \"__meta\" is reserved",
+            ),
+            (
+                "[remote_write.\"a b\"]\nurl = \"x\"",
+                "invalid remote write endpoint name",
+            ),
+            (
+                "[[metric_relabel_configs]]\naction = \"hashmod\"\ntarget_label = \"shard\"",
+                "invalid metric_relabel_configs[0]: hashmod requires a modulus",
+            ),
+            (
+                "no_remote_write = true",
+                "--no-remote-write requires --listen-address",
+            ),
+        ] {
+            let err = load(toml, &[]).unwrap_err();
+            assert!(err.to_string().starts_with(message), "{toml:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn patterns_are_anchored() {
+        let pattern = Pattern::new("eth.").unwrap();
+        assert!(pattern.is_match("eth0"));
+        assert!(!pattern.is_match("veth0"));
+        assert!(!pattern.is_match("eth0.1"));
+        assert!(Pattern::new("a|b").unwrap().is_match("b"));
+    }
+}
@@ -0,0 +1,721 @@
+/// Whether a metric only ever goes up or can move freely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+impl MetricKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+/// Static description of a metric emitted by agemon.
+#[derive(Debug, Clone, Copy)]
+pub struct MetricDescriptor {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub help: &'static str,
+    /// Base unit the metric name ends in (before `_total`), empty if it has none
+    pub unit: &'static str,
+}
+
+/// Describe a counter, for use in `const` tables.
+pub const fn counter(
+    name: &'static str,
+    unit: &'static str,
+    help: &'static str,
+) -> MetricDescriptor {
+    MetricDescriptor {
+        name,
+        kind: MetricKind::Counter,
+        help,
+        unit,
+    }
+}
+
+/// Describe a gauge, for use in `const` tables.
+pub const fn gauge(name: &'static str, unit: &'static str, help: &'static str) -> MetricDescriptor {
+    MetricDescriptor {
+        name,
+        kind: MetricKind::Gauge,
+        help,
+        unit,
+    }
+}
+
+/// CPU usage from the `cpu` collector.
+pub const CPU: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_cpu_usage_percent",
+        "percent",
+        "Global CPU usage percentage (0-100)",
+    ),
+    gauge("agemon_cpu_count", "", "Number of logical CPU cores"),
+    gauge(
+        "agemon_cpu_core_usage_percent",
+        "percent",
+        "Per-core CPU usage percentage",
+    ),
+];
+
+/// Memory and swap from the `memory` collector.
+pub const MEMORY: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_memory_total_bytes",
+        "bytes",
+        "Total physical memory in bytes",
+    ),
+    gauge(
+        "agemon_memory_used_bytes",
+        "bytes",
+        "Used physical memory in bytes",
+    ),
+    gauge(
+        "agemon_memory_free_bytes",
+        "bytes",
+        "Free physical memory in bytes",
+    ),
+    gauge(
+        "agemon_memory_available_bytes",
+        "bytes",
+        "Available physical memory in bytes (includes cached/buffered)",
+    ),
+    gauge(
+        "agemon_memory_usage_ratio",
+        "ratio",
+        "Memory usage ratio (0.0-1.0)",
+    ),
+    gauge(
+        "agemon_swap_total_bytes",
+        "bytes",
+        "Total swap space in bytes",
+    ),
+    gauge(
+        "agemon_swap_used_bytes",
+        "bytes",
+        "Used swap space in bytes",
+    ),
+    gauge(
+        "agemon_swap_free_bytes",
+        "bytes",
+        "Free swap space in bytes",
+    ),
+    gauge(
+        "agemon_swap_usage_ratio",
+        "ratio",
+        "Swap usage ratio (0.0-1.0)",
+    ),
+];
+
+/// Filesystem space from the `disk` collector.
+pub const DISK: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_disk_total_bytes",
+        "bytes",
+        "Total disk space in bytes",
+    ),
+    gauge(
+        "agemon_disk_available_bytes",
+        "bytes",
+        "Available disk space in bytes",
+    ),
+    gauge(
+        "agemon_disk_used_bytes",
+        "bytes",
+        "Used disk space in bytes",
+    ),
+    gauge(
+        "agemon_disk_usage_ratio",
+        "ratio",
+        "Disk usage ratio (0.0-1.0)",
+    ),
+    gauge(
+        "agemon_disk_is_removable",
+        "",
+        "Whether the disk is removable (1=yes, 0=no)",
+    ),
+    gauge(
+        "agemon_disk_readonly",
+        "",
+        "Whether the filesystem is mounted read-only (1=yes, 0=no)",
+    ),
+    gauge(
+        "agemon_disk_inodes_total",
+        "",
+        "Total inodes on the filesystem",
+    ),
+    gauge(
+        "agemon_disk_inodes_free",
+        "",
+        "Free inodes on the filesystem",
+    ),
+];
+
+/// Process I/O totals from the `disk_io` collector.
+pub const DISK_IO: &[MetricDescriptor] = &[
+    counter(
+        "agemon_disk_io_read_bytes_total",
+        "bytes",
+        "Total bytes read from disk (aggregated from all processes)",
+    ),
+    counter(
+        "agemon_disk_io_written_bytes_total",
+        "bytes",
+        "Total bytes written to disk (aggregated from all processes)",
+    ),
+    gauge(
+        "agemon_disk_io_read_bytes_per_sec",
+        "",
+        "Bytes read per second since last refresh",
+    ),
+    gauge(
+        "agemon_disk_io_written_bytes_per_sec",
+        "",
+        "Bytes written per second since last refresh",
+    ),
+];
+
+/// Block device counters from the `diskstats` collector.
+pub const DISKSTATS: &[MetricDescriptor] = &[
+    counter(
+        "agemon_disk_reads_completed_total",
+        "",
+        "Reads completed successfully per block device",
+    ),
+    counter(
+        "agemon_disk_reads_merged_total",
+        "",
+        "Adjacent reads merged for efficiency per block device",
+    ),
+    counter(
+        "agemon_disk_read_bytes_total",
+        "bytes",
+        "Bytes read successfully per block device",
+    ),
+    counter(
+        "agemon_disk_read_time_seconds_total",
+        "seconds",
+        "Time spent reading per block device in seconds",
+    ),
+    counter(
+        "agemon_disk_writes_completed_total",
+        "",
+        "Writes completed successfully per block device",
+    ),
+    counter(
+        "agemon_disk_writes_merged_total",
+        "",
+        "Adjacent writes merged for efficiency per block device",
+    ),
+    counter(
+        "agemon_disk_written_bytes_total",
+        "bytes",
+        "Bytes written successfully per block device",
+    ),
+    counter(
+        "agemon_disk_write_time_seconds_total",
+        "seconds",
+        "Time spent writing per block device in seconds",
+    ),
+    gauge(
+        "agemon_disk_io_now",
+        "",
+        "I/Os currently in progress per block device",
+    ),
+    counter(
+        "agemon_disk_io_time_seconds_total",
+        "seconds",
+        "Time spent doing I/Os per block device in seconds",
+    ),
+    counter(
+        "agemon_disk_io_time_weighted_seconds_total",
+        "seconds",
+        "Time spent doing I/Os weighted by the number of in-flight I/Os, in seconds",
+    ),
+];
+
+/// Interface counters from the `network` collector.
+pub const NETWORK: &[MetricDescriptor] = &[
+    counter(
+        "agemon_network_received_bytes_total",
+        "bytes",
+        "Total bytes received on interface",
+    ),
+    counter(
+        "agemon_network_transmitted_bytes_total",
+        "bytes",
+        "Total bytes transmitted on interface",
+    ),
+    counter(
+        "agemon_network_received_packets_total",
+        "",
+        "Total packets received on interface",
+    ),
+    counter(
+        "agemon_network_transmitted_packets_total",
+        "",
+        "Total packets transmitted on interface",
+    ),
+    counter(
+        "agemon_network_received_errors_total",
+        "",
+        "Total receive errors on interface",
+    ),
+    counter(
+        "agemon_network_transmitted_errors_total",
+        "",
+        "Total transmit errors on interface",
+    ),
+];
+
+/// Sensor readings from the `temperature` collector.
+pub const TEMPERATURE: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_temperature_celsius",
+        "celsius",
+        "Current temperature of the sensor",
+    ),
+    gauge(
+        "agemon_temperature_max_celsius",
+        "celsius",
+        "Maximum observed temperature of the sensor",
+    ),
+    gauge(
+        "agemon_temperature_critical_celsius",
+        "celsius",
+        "Critical threshold temperature of the sensor",
+    ),
+];
+
+/// Uptime, load and OS information from the `system` collector.
+pub const SYSTEM: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_system_uptime_seconds",
+        "seconds",
+        "System uptime in seconds",
+    ),
+    gauge(
+        "agemon_system_boot_time_seconds",
+        "seconds",
+        "System boot time as Unix timestamp",
+    ),
+    gauge("agemon_load_average_1m", "", "1-minute load average"),
+    gauge("agemon_load_average_5m", "", "5-minute load average"),
+    gauge("agemon_load_average_15m", "", "15-minute load average"),
+    gauge("agemon_info", "", "System information (always 1)"),
+];
+
+/// Kernel statistics from the `procfs` collector.
+pub const PROCFS: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_tcp_connections",
+        "",
+        "IPv4 TCP connections by state",
+    ),
+    gauge(
+        "agemon_tcp6_connections",
+        "",
+        "IPv6 TCP connections by state",
+    ),
+    gauge(
+        "agemon_file_descriptors_allocated",
+        "",
+        "Allocated file descriptors system-wide",
+    ),
+    gauge(
+        "agemon_file_descriptors_max",
+        "",
+        "Maximum number of file descriptors system-wide",
+    ),
+    counter(
+        "agemon_cpu_seconds_total",
+        "seconds",
+        "Seconds each CPU spent in each mode",
+    ),
+    counter(
+        "agemon_cpu_guest_seconds_total",
+        "seconds",
+        "Seconds each CPU spent running virtual machines, also counted in the user and nice modes",
+    ),
+    counter(
+        "agemon_context_switches_total",
+        "",
+        "Total context switches",
+    ),
+    counter(
+        "agemon_processes_forked_total",
+        "",
+        "Total processes forked since boot",
+    ),
+    gauge("agemon_procs_running", "", "Processes in runnable state"),
+    gauge(
+        "agemon_procs_blocked",
+        "",
+        "Processes blocked waiting for I/O",
+    ),
+    gauge(
+        "agemon_psi_cpu_some_avg10",
+        "",
+        "Share of time some tasks stalled on CPU over 10s (percent)",
+    ),
+    gauge(
+        "agemon_psi_cpu_some_avg60",
+        "",
+        "Share of time some tasks stalled on CPU over 60s (percent)",
+    ),
+    gauge(
+        "agemon_psi_cpu_some_avg300",
+        "",
+        "Share of time some tasks stalled on CPU over 300s (percent)",
+    ),
+    counter(
+        "agemon_psi_cpu_some_total_us",
+        "",
+        "Total time some tasks stalled on CPU in microseconds",
+    ),
+    gauge(
+        "agemon_psi_memory_some_avg10",
+        "",
+        "Share of time some tasks stalled on memory over 10s (percent)",
+    ),
+    gauge(
+        "agemon_psi_memory_some_avg60",
+        "",
+        "Share of time some tasks stalled on memory over 60s (percent)",
+    ),
+    gauge(
+        "agemon_psi_memory_some_avg300",
+        "",
+        "Share of time some tasks stalled on memory over 300s (percent)",
+    ),
+    counter(
+        "agemon_psi_memory_some_total_us",
+        "",
+        "Total time some tasks stalled on memory in microseconds",
+    ),
+    gauge(
+        "agemon_psi_memory_full_avg10",
+        "",
+        "Share of time all non-idle tasks stalled on memory over 10s (percent)",
+    ),
+    gauge(
+        "agemon_psi_memory_full_avg60",
+        "",
+        "Share of time all non-idle tasks stalled on memory over 60s (percent)",
+    ),
+    gauge(
+        "agemon_psi_memory_full_avg300",
+        "",
+        "Share of time all non-idle tasks stalled on memory over 300s (percent)",
+    ),
+    counter(
+        "agemon_psi_memory_full_total_us",
+        "",
+        "Total time all non-idle tasks stalled on memory in microseconds",
+    ),
+    gauge(
+        "agemon_psi_io_some_avg10",
+        "",
+        "Share of time some tasks stalled on I/O over 10s (percent)",
+    ),
+    gauge(
+        "agemon_psi_io_some_avg60",
+        "",
+        "Share of time some tasks stalled on I/O over 60s (percent)",
+    ),
+    gauge(
+        "agemon_psi_io_some_avg300",
+        "",
+        "Share of time some tasks stalled on I/O over 300s (percent)",
+    ),
+    counter(
+        "agemon_psi_io_some_total_us",
+        "",
+        "Total time some tasks stalled on I/O in microseconds",
+    ),
+    gauge(
+        "agemon_psi_io_full_avg10",
+        "",
+        "Share of time all non-idle tasks stalled on I/O over 10s (percent)",
+    ),
+    gauge(
+        "agemon_psi_io_full_avg60",
+        "",
+        "Share of time all non-idle tasks stalled on I/O over 60s (percent)",
+    ),
+    gauge(
+        "agemon_psi_io_full_avg300",
+        "",
+        "Share of time all non-idle tasks stalled on I/O over 300s (percent)",
+    ),
+    counter(
+        "agemon_psi_io_full_total_us",
+        "",
+        "Total time all non-idle tasks stalled on I/O in microseconds",
+    ),
+    counter("agemon_vmstat_pgfault_total", "", "Total page faults"),
+    counter(
+        "agemon_vmstat_pgmajfault_total",
+        "",
+        "Total major page faults",
+    ),
+    counter(
+        "agemon_vmstat_pgpgin_total",
+        "",
+        "Total kilobytes paged in from disk",
+    ),
+    counter(
+        "agemon_vmstat_pgpgout_total",
+        "",
+        "Total kilobytes paged out to disk",
+    ),
+    counter("agemon_vmstat_pswpin_total", "", "Total pages swapped in"),
+    counter("agemon_vmstat_pswpout_total", "", "Total pages swapped out"),
+    counter(
+        "agemon_vmstat_oom_kill_total",
+        "",
+        "Total processes killed by the OOM killer",
+    ),
+    counter(
+        "agemon_tcp_retrans_segs_total",
+        "",
+        "Total TCP segments retransmitted",
+    ),
+    counter(
+        "agemon_tcp_in_segs_total",
+        "",
+        "Total TCP segments received",
+    ),
+    counter("agemon_tcp_out_segs_total", "", "Total TCP segments sent"),
+    counter(
+        "agemon_tcp_active_opens_total",
+        "",
+        "Total active TCP connection openings",
+    ),
+    counter(
+        "agemon_tcp_passive_opens_total",
+        "",
+        "Total passive TCP connection openings",
+    ),
+    gauge(
+        "agemon_tcp_curr_estab",
+        "",
+        "TCP connections currently established or in CLOSE-WAIT",
+    ),
+    counter(
+        "agemon_udp_in_datagrams_total",
+        "",
+        "Total UDP datagrams received",
+    ),
+    counter(
+        "agemon_udp_out_datagrams_total",
+        "",
+        "Total UDP datagrams sent",
+    ),
+    counter(
+        "agemon_udp_in_errors_total",
+        "",
+        "Total UDP datagrams that could not be delivered",
+    ),
+    gauge(
+        "agemon_entropy_available",
+        "",
+        "Available kernel entropy in bits",
+    ),
+];
+
+/// Per-cgroup usage from the `cgroup` collector.
+pub const CGROUP: &[MetricDescriptor] = &[
+    counter(
+        "agemon_cgroup_cpu_usage_seconds_total",
+        "seconds",
+        "CPU time consumed by the cgroup in seconds",
+    ),
+    counter(
+        "agemon_cgroup_cpu_user_seconds_total",
+        "seconds",
+        "User CPU time consumed by the cgroup in seconds",
+    ),
+    counter(
+        "agemon_cgroup_cpu_system_seconds_total",
+        "seconds",
+        "System CPU time consumed by the cgroup in seconds",
+    ),
+    counter(
+        "agemon_cgroup_cpu_periods_total",
+        "",
+        "Enforcement periods elapsed for the cgroup's CPU limit",
+    ),
+    counter(
+        "agemon_cgroup_cpu_throttled_periods_total",
+        "",
+        "Enforcement periods in which the cgroup was throttled",
+    ),
+    counter(
+        "agemon_cgroup_cpu_throttled_seconds_total",
+        "seconds",
+        "Time the cgroup was throttled in seconds",
+    ),
+    gauge(
+        "agemon_cgroup_memory_current_bytes",
+        "bytes",
+        "Memory charged to the cgroup and its descendants in bytes",
+    ),
+    gauge(
+        "agemon_cgroup_memory_max_bytes",
+        "bytes",
+        "Hard memory limit of the cgroup in bytes",
+    ),
+    counter(
+        "agemon_cgroup_io_read_bytes_total",
+        "bytes",
+        "Bytes read by the cgroup per device",
+    ),
+    counter(
+        "agemon_cgroup_io_written_bytes_total",
+        "bytes",
+        "Bytes written by the cgroup per device",
+    ),
+    counter(
+        "agemon_cgroup_io_reads_total",
+        "",
+        "Read I/Os issued by the cgroup per device",
+    ),
+    counter(
+        "agemon_cgroup_io_writes_total",
+        "",
+        "Write I/Os issued by the cgroup per device",
+    ),
+    counter(
+        "agemon_cgroup_psi_some_total_us",
+        "",
+        "Time in microseconds some tasks of the cgroup were stalled on the resource",
+    ),
+    counter(
+        "agemon_cgroup_psi_full_total_us",
+        "",
+        "Time in microseconds all tasks of the cgroup were stalled on the resource",
+    ),
+];
+
+/// Top processes from the `processes` collector.
+pub const PROCESSES: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_process_count",
+        "",
+        "Total number of running processes",
+    ),
+    gauge(
+        "agemon_process_cpu_usage_percent",
+        "percent",
+        "CPU usage percentage of the top processes by name",
+    ),
+    gauge(
+        "agemon_process_memory_bytes",
+        "bytes",
+        "Resident memory of the top processes by name in bytes",
+    ),
+];
+
+/// Run time and resource usage of the agent and its collectors, reported by the agent itself.
+pub const AGENT: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_agent_collector_duration_seconds",
+        "seconds",
+        "Duration of the last run per collector",
+    ),
+    gauge(
+        "agemon_agent_collector_series",
+        "",
+        "Series emitted by the last run per collector",
+    ),
+    counter(
+        "agemon_agent_collector_errors_total",
+        "",
+        "Failed reads and panics per collector",
+    ),
+    gauge(
+        "agemon_agent_resident_memory_bytes",
+        "bytes",
+        "Resident memory of the agent in bytes",
+    ),
+    counter(
+        "agemon_agent_cpu_seconds_total",
+        "seconds",
+        "CPU time used by the agent in seconds",
+    ),
+];
+
+/// Queue and push state of every remote write endpoint, reported by the agent itself under the
+/// same `agemon_agent_` prefix as [`AGENT`].
+pub const REMOTE_WRITE: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_agent_queue_batches",
+        "",
+        "Batches waiting in the queue per endpoint",
+    ),
+    counter(
+        "agemon_agent_queue_dropped_batches_total",
+        "",
+        "Batches dropped because the queue was full per endpoint",
+    ),
+    counter(
+        "agemon_agent_sent_samples_total",
+        "",
+        "Samples pushed successfully per endpoint",
+    ),
+    counter(
+        "agemon_agent_failed_batches_total",
+        "",
+        "Batches that could not be pushed per endpoint, after retries",
+    ),
+    counter(
+        "agemon_agent_push_attempts_total",
+        "",
+        "HTTP requests made per endpoint, including retries and buffer replays",
+    ),
+    counter(
+        "agemon_agent_push_failures_total",
+        "",
+        "Failed HTTP requests per endpoint by status code, `error` if there was no response",
+    ),
+    counter(
+        "agemon_agent_sent_bytes_total",
+        "bytes",
+        "Compressed request bytes sent per endpoint",
+    ),
+    gauge(
+        "agemon_agent_push_duration_seconds",
+        "seconds",
+        "Duration of the last HTTP request per endpoint",
+    ),
+    gauge(
+        "agemon_agent_last_push_success_timestamp_seconds",
+        "seconds",
+        "Unix time of the last successful HTTP request per endpoint",
+    ),
+];
+
+/// State of the on-disk buffer of every endpoint, reported by the agent itself.
+pub const BUFFER: &[MetricDescriptor] = &[
+    gauge(
+        "agemon_agent_buffer_batches",
+        "",
+        "Batches waiting in the on-disk buffer per endpoint",
+    ),
+    gauge(
+        "agemon_agent_buffer_size_bytes",
+        "bytes",
+        "Size of the on-disk buffer per endpoint in bytes",
+    ),
+    counter(
+        "agemon_agent_buffer_dropped_batches_total",
+        "",
+        "Batches discarded from the on-disk buffer per endpoint without being pushed",
+    ),
+];
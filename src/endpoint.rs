@@ -0,0 +1,558 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use miette::{Result, miette};
+use prometheus_remote_write::{LABEL_NAME, TimeSeries};
+use reqwest::blocking::Client;
+use tracing::{error, info, info_span, warn};
+
+use crate::{
+    auth::RequestHeaders,
+    buffer::{DiskBuffer, DropReason},
+    collector::Sink,
+    config::{RemoteWriteConfig, RemoteWriteProtocol},
+    descriptors::MetricDescriptor,
+    proto::{MetricMetadata, WriteRequest},
+    remote_write::{PushStats, push_metrics},
+    retry::{PushError, RetryPolicy},
+    shutdown::Shutdown,
+    tls::HttpClient,
+};
+
+/// Batches waiting for an endpoint before new ones are dropped.
+const QUEUE_CAPACITY: usize = 8;
+
+const DROP_REASONS: [DropReason; 4] = [
+    DropReason::Age,
+    DropReason::Size,
+    DropReason::Corrupt,
+    DropReason::Rejected,
+];
+
+/// Settings shared by every endpoint.
+#[derive(Debug, Clone)]
+pub(crate) struct EndpointSettings {
+    pub policy: RetryPolicy,
+    pub metadata_interval: Duration,
+    /// Descriptors of everything that can end up in a batch
+    pub descriptors: Arc<[MetricDescriptor]>,
+    pub buffer_max_bytes: u64,
+    pub buffer_max_age: Duration,
+}
+
+#[derive(Debug, Default)]
+struct EndpointStats {
+    queued_batches: u64,
+    queue_dropped_batches: u64,
+    samples_sent: u64,
+    failed_pushes: u64,
+    /// Batches lost after shutdown was triggered, for lack of a buffer to keep them
+    shutdown_lost_batches: u64,
+    /// When pushing the current batch from the queue or the buffer started
+    busy_since: Option<Instant>,
+    /// Error of the last batch, `None` if it was pushed or none was yet
+    last_error: Option<String>,
+    push: PushStats,
+    buffer: Option<BufferStats>,
+}
+
+#[derive(Debug)]
+struct BufferStats {
+    batches: usize,
+    size_bytes: u64,
+    dropped_batches: [u64; 4],
+}
+
+impl BufferStats {
+    fn of(buffer: &DiskBuffer) -> Self {
+        BufferStats {
+            batches: buffer.len(),
+            size_bytes: buffer.size_bytes(),
+            dropped_batches: DROP_REASONS.map(|reason| buffer.dropped_batches(reason)),
+        }
+    }
+}
+
+/// Handle to a remote write endpoint pushing batches from its own thread, so a slow endpoint
+/// does not delay collection or the other endpoints.
+pub(crate) struct Endpoint {
+    name: String,
+    sender: SyncSender<Arc<Vec<TimeSeries>>>,
+    stats: Arc<Mutex<EndpointStats>>,
+    worker: JoinHandle<()>,
+}
+
+impl Endpoint {
+    pub fn spawn(
+        name: String,
+        config: RemoteWriteConfig,
+        buffer_dir: Option<PathBuf>,
+        settings: EndpointSettings,
+        shutdown: Shutdown,
+    ) -> Result<Self> {
+        let buffer = buffer_dir
+            .as_deref()
+            .map(|dir| DiskBuffer::open(dir, settings.buffer_max_bytes, settings.buffer_max_age))
+            .transpose()?;
+        if let Some(buffer) = &buffer {
+            info!(
+                "buffering failed pushes to {} in {} ({} batches pending)",
+                name,
+                buffer_dir.as_ref().unwrap().display(),
+                buffer.len()
+            );
+        }
+
+        let headers = RequestHeaders::new(&name, &config)?;
+        let client = HttpClient::new(&name, &config.url, config.tls.clone())?;
+
+        let stats = Arc::new(Mutex::new(EndpointStats {
+            buffer: buffer.as_ref().map(BufferStats::of),
+            ..Default::default()
+        }));
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let worker = Worker {
+            name: name.clone(),
+            config,
+            headers,
+            settings,
+            client,
+            buffer,
+            stats: stats.clone(),
+            push_stats: PushStats::default(),
+            metadata_sent: None,
+            shutdown,
+            last_push_failed: false,
+            not_before: None,
+        };
+        let worker = thread::Builder::new()
+            .name(format!("remote-write-{}", name))
+            .spawn(move || worker.run(receiver))
+            .map_err(|err| miette!("failed to start remote write thread for {}: {}", name, err))?;
+
+        Ok(Endpoint {
+            name,
+            sender,
+            stats,
+            worker,
+        })
+    }
+
+    /// Queue a batch for pushing, dropping it if the endpoint is too far behind.
+    pub fn send(&self, timeseries: Arc<Vec<TimeSeries>>) {
+        let mut stats = self.stats.lock().unwrap();
+        match self.sender.try_send(timeseries) {
+            Ok(()) => stats.queued_batches += 1,
+            Err(TrySendError::Full(_)) => {
+                warn!("{}: queue full, dropping batch", self.name);
+                stats.queue_dropped_batches += 1;
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                error!("{}: remote write thread stopped, dropping batch", self.name);
+                stats.queue_dropped_batches += 1;
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether a single batch has been pushing for longer than `timeout`, including its retries,
+    /// or the remote write thread is gone. Replaying a long buffer is progress, not a stall.
+    pub fn stalled(&self, timeout: Duration) -> bool {
+        let busy_since = self.stats.lock().unwrap().busy_since;
+        self.worker.is_finished() || busy_since.is_some_and(|since| since.elapsed() > timeout)
+    }
+
+    /// Outcome of the last push, for `systemctl status`.
+    pub fn status(&self) -> String {
+        let stats = self.stats.lock().unwrap();
+        let result = match (&stats.last_error, stats.push.last_success) {
+            (Some(err), _) => format!("push failed: {}", err),
+            (None, Some(_)) => "pushed".to_string(),
+            (None, None) => "waiting for the first push".to_string(),
+        };
+        format!("{}: {}", self.name, result)
+    }
+
+    /// Stop accepting batches and wait for the queued ones to be pushed or buffered, returns
+    /// whether that finished before `deadline` without losing any.
+    pub fn close(self, deadline: Instant) -> bool {
+        drop(self.sender);
+        let queued = self.stats.lock().unwrap().queued_batches;
+        if queued > 0 {
+            info!("{}: flushing {} queued batches", self.name, queued);
+        }
+        while !self.worker.is_finished() {
+            if Instant::now() >= deadline {
+                let queued = self.stats.lock().unwrap().queued_batches;
+                error!(
+                    "{}: shutdown timeout expired with a push in flight and {} batches queued",
+                    self.name, queued
+                );
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let lost = self.stats.lock().unwrap().shutdown_lost_batches;
+        if lost > 0 {
+            error!(
+                "{}: {} batches could not be pushed during shutdown, set --buffer-dir to keep them",
+                self.name, lost
+            );
+        }
+        lost == 0
+    }
+
+    /// Emit the queue, push and buffer metrics of this endpoint.
+    pub fn collect_metrics(&self, sink: &mut Sink) {
+        let stats = self.stats.lock().unwrap();
+        let labels = [("endpoint", self.name.as_str())];
+
+        // agemon_agent_queue_batches: Batches waiting in the queue
+        sink.push_with_labels(
+            "agemon_agent_queue_batches",
+            stats.queued_batches as f64,
+            &labels,
+        );
+
+        // agemon_agent_queue_dropped_batches_total: Batches dropped on a full queue (counter)
+        sink.push_with_labels(
+            "agemon_agent_queue_dropped_batches_total",
+            stats.queue_dropped_batches as f64,
+            &labels,
+        );
+
+        // agemon_agent_sent_samples_total: Samples pushed successfully (counter)
+        sink.push_with_labels(
+            "agemon_agent_sent_samples_total",
+            stats.samples_sent as f64,
+            &labels,
+        );
+
+        // agemon_agent_failed_batches_total: Batches not pushed after retries (counter)
+        sink.push_with_labels(
+            "agemon_agent_failed_batches_total",
+            stats.failed_pushes as f64,
+            &labels,
+        );
+
+        // agemon_agent_push_attempts_total: HTTP requests made, including retries (counter)
+        sink.push_with_labels(
+            "agemon_agent_push_attempts_total",
+            stats.push.attempts as f64,
+            &labels,
+        );
+
+        // agemon_agent_push_failures_total: Failed HTTP requests by status code (counter)
+        for (status, failures) in &stats.push.failures {
+            sink.push_with_labels(
+                "agemon_agent_push_failures_total",
+                *failures as f64,
+                &[("endpoint", self.name.as_str()), ("status", status)],
+            );
+        }
+
+        // agemon_agent_sent_bytes_total: Compressed request bytes sent (counter)
+        sink.push_with_labels(
+            "agemon_agent_sent_bytes_total",
+            stats.push.bytes_sent as f64,
+            &labels,
+        );
+
+        // agemon_agent_push_duration_seconds: Duration of the last HTTP request
+        if let Some(duration) = stats.push.last_duration {
+            sink.push_with_labels(
+                "agemon_agent_push_duration_seconds",
+                duration.as_secs_f64(),
+                &labels,
+            );
+        }
+
+        // agemon_agent_last_push_success_timestamp_seconds: Unix time of the last successful push
+        if let Some(success) = stats.push.last_success {
+            let timestamp = success.duration_since(UNIX_EPOCH).unwrap_or_default();
+            sink.push_with_labels(
+                "agemon_agent_last_push_success_timestamp_seconds",
+                timestamp.as_secs_f64(),
+                &labels,
+            );
+        }
+
+        let Some(buffer) = &stats.buffer else {
+            return;
+        };
+
+        // agemon_agent_buffer_batches: Batches waiting in the on-disk buffer
+        sink.push_with_labels(
+            "agemon_agent_buffer_batches",
+            buffer.batches as f64,
+            &labels,
+        );
+
+        // agemon_agent_buffer_size_bytes: Size of the on-disk buffer in bytes
+        sink.push_with_labels(
+            "agemon_agent_buffer_size_bytes",
+            buffer.size_bytes as f64,
+            &labels,
+        );
+
+        // agemon_agent_buffer_dropped_batches_total: Batches never pushed (counter)
+        for (reason, dropped) in DROP_REASONS.iter().zip(buffer.dropped_batches) {
+            sink.push_with_labels(
+                "agemon_agent_buffer_dropped_batches_total",
+                dropped as f64,
+                &[
+                    ("endpoint", self.name.as_str()),
+                    ("reason", reason.as_str()),
+                ],
+            );
+        }
+    }
+}
+
+struct Worker {
+    name: String,
+    config: RemoteWriteConfig,
+    headers: RequestHeaders,
+    settings: EndpointSettings,
+    client: HttpClient,
+    buffer: Option<DiskBuffer>,
+    stats: Arc<Mutex<EndpointStats>>,
+    push_stats: PushStats,
+    metadata_sent: Option<Instant>,
+    shutdown: Shutdown,
+    last_push_failed: bool,
+    /// Until when the endpoint asked not to be sent anything, from its last `Retry-After`
+    not_before: Option<Instant>,
+}
+
+impl Worker {
+    fn run(mut self, receiver: Receiver<Arc<Vec<TimeSeries>>>) {
+        let _span = info_span!("remote_write", endpoint = %self.name).entered();
+        for timeseries in receiver {
+            {
+                let mut stats = self.stats.lock().unwrap();
+                stats.queued_batches -= 1;
+            }
+            // A Retry-After longer than the retries wait for still has to be honoured
+            if let Some(wait) = self
+                .not_before
+                .and_then(|not_before| not_before.checked_duration_since(Instant::now()))
+            {
+                if let Some(buffer) = &mut self.buffer {
+                    info!("endpoint asked to wait {:?}, buffering batch", wait);
+                    if let Err(err) = buffer.enqueue(Arc::unwrap_or_clone(timeseries)) {
+                        warn!("failed to buffer batch: {}", err);
+                    }
+                    self.stats.lock().unwrap().buffer = Some(BufferStats::of(buffer));
+                    continue;
+                }
+                info!("endpoint asked to wait, pushing in {:?}", wait);
+                self.shutdown.sleep(wait);
+            }
+            start_batch(&self.stats);
+
+            let metadata = self.due_metadata(&timeseries);
+            let sent_metadata = !metadata.is_empty();
+            let samples: usize = timeseries.iter().map(|series| series.samples.len()).sum();
+            let result = self.push(Arc::unwrap_or_clone(timeseries), metadata);
+
+            self.last_push_failed = result.is_err();
+            self.not_before = match &result {
+                Err(PushError::Retryable {
+                    retry_after: Some(retry_after),
+                    ..
+                }) => Some(Instant::now() + *retry_after),
+                _ => None,
+            };
+            let mut stats = self.stats.lock().unwrap();
+            match result {
+                Ok(()) => {
+                    stats.samples_sent += samples as u64;
+                    stats.last_error = None;
+                    if sent_metadata {
+                        self.metadata_sent = Some(Instant::now());
+                    }
+                }
+                Err(err) => {
+                    stats.failed_pushes += 1;
+                    if self.shutdown.is_triggered() && self.buffer.is_none() {
+                        stats.shutdown_lost_batches += 1;
+                    }
+                    error!("push failed: {}", err);
+                    stats.last_error = Some(err.to_string());
+                }
+            }
+            stats.busy_since = None;
+            stats.push = self.push_stats.clone();
+            stats.buffer = self.buffer.as_ref().map(BufferStats::of);
+        }
+    }
+
+    /// Metadata for the metrics in this batch, if it is time to send it again.
+    ///
+    /// Remote write 2.0 attaches metadata to every series, so it is always due.
+    fn due_metadata(&self, timeseries: &[TimeSeries]) -> Vec<MetricMetadata> {
+        let interval = self.settings.metadata_interval;
+        let due = match self.config.protocol {
+            RemoteWriteProtocol::V1 => {
+                !interval.is_zero()
+                    && self
+                        .metadata_sent
+                        .is_none_or(|sent| sent.elapsed() >= interval)
+            }
+            RemoteWriteProtocol::V2 => true,
+        };
+        if !due {
+            return vec![];
+        }
+
+        let names: HashSet<&str> = timeseries
+            .iter()
+            .flat_map(|series| &series.labels)
+            .filter(|label| label.name == LABEL_NAME)
+            .map(|label| label.value.as_str())
+            .collect();
+        self.settings
+            .descriptors
+            .iter()
+            .filter(|descriptor| names.contains(descriptor.name))
+            .map(MetricMetadata::from)
+            .collect()
+    }
+
+    fn push(
+        &mut self,
+        timeseries: Vec<TimeSeries>,
+        metadata: Vec<MetricMetadata>,
+    ) -> Result<(), PushError> {
+        // Once the endpoint failed during shutdown, keep the deadline for writing the rest of the
+        // queue to the buffer instead of trying again
+        let skip = self.shutdown.is_triggered() && self.last_push_failed;
+        let skipped = || PushError::Retryable {
+            reason: "skipped after a failed push during shutdown".to_string(),
+            retry_after: None,
+        };
+
+        let Some(buffer) = &mut self.buffer else {
+            if skip {
+                return Err(skipped());
+            }
+            let request = WriteRequest {
+                timeseries,
+                metadata,
+            };
+            return push_metrics(
+                self.client.get(),
+                &self.config,
+                &mut self.headers,
+                &self.settings.policy,
+                &mut self.push_stats,
+                &self.shutdown,
+                request,
+            );
+        };
+
+        // Replay older batches first so samples arrive in timestamp order
+        let client = self.client.get();
+        let headers = &mut self.headers;
+        let policy = &self.settings.policy;
+        let stats = &mut self.push_stats;
+        let shutdown = &self.shutdown;
+        let result = if skip {
+            Err(skipped())
+        } else {
+            replay_buffer(
+                client,
+                &self.config,
+                headers,
+                policy,
+                stats,
+                shutdown,
+                buffer,
+                &self.stats,
+            )
+            .and_then(|()| {
+                start_batch(&self.stats);
+                let request = WriteRequest {
+                    timeseries: timeseries.clone(),
+                    metadata,
+                };
+                push_metrics(
+                    client,
+                    &self.config,
+                    headers,
+                    policy,
+                    stats,
+                    shutdown,
+                    request,
+                )
+            })
+        };
+        match result {
+            Ok(()) => Ok(()),
+            // The endpoint will never accept this batch, buffering it would only block the queue
+            Err(err @ PushError::Permanent(_)) => Err(err),
+            Err(err) => {
+                if let Err(buffer_err) = buffer.enqueue(timeseries) {
+                    warn!("failed to buffer batch: {}", buffer_err);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Start timing a batch for [`Endpoint::stalled`].
+fn start_batch(stats: &Mutex<EndpointStats>) {
+    stats.lock().unwrap().busy_since = Some(Instant::now());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn replay_buffer(
+    client: &Client,
+    config: &RemoteWriteConfig,
+    headers: &mut RequestHeaders,
+    policy: &RetryPolicy,
+    stats: &mut PushStats,
+    shutdown: &Shutdown,
+    buffer: &mut DiskBuffer,
+    endpoint_stats: &Mutex<EndpointStats>,
+) -> Result<(), PushError> {
+    if buffer.is_empty() {
+        return Ok(());
+    }
+
+    info!("replaying {} buffered batches", buffer.len());
+    while let Some(timeseries) = buffer.front() {
+        start_batch(endpoint_stats);
+        let samples: usize = timeseries.iter().map(|series| series.samples.len()).sum();
+        let request = WriteRequest {
+            timeseries,
+            metadata: vec![],
+        };
+        match push_metrics(client, config, headers, policy, stats, shutdown, request) {
+            Ok(()) => {
+                endpoint_stats.lock().unwrap().samples_sent += samples as u64;
+                buffer.remove_front();
+            }
+            Err(PushError::Permanent(reason)) => {
+                warn!("dropping buffered batch rejected by endpoint: {}", reason);
+                buffer.discard_front(DropReason::Rejected);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,399 @@
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use miette::{Result, miette};
+use prometheus_remote_write::{LABEL_NAME, TimeSeries};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use tracing::{debug, info, warn};
+
+use crate::descriptors::MetricDescriptor;
+
+const TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Connections served at once, further ones are closed right away.
+const MAX_CONNECTIONS: usize = 16;
+
+/// How long a client may take to send its request, scrapers send it immediately.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Serves the most recently collected metrics on `/metrics` for Prometheus to scrape.
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    latest: Arc<Mutex<Vec<TimeSeries>>>,
+}
+
+impl Exporter {
+    /// Bind the listener and serve scrapes from a background thread, describing metrics with
+    /// the given descriptors.
+    pub fn spawn(addr: SocketAddr, descriptors: Vec<MetricDescriptor>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|err| miette!("failed to listen on {}: {}", addr, err))?;
+        info!("serving metrics on http://{}/metrics", addr);
+
+        let exporter = Exporter {
+            latest: Arc::new(Mutex::new(vec![])),
+        };
+        let latest = exporter.latest.clone();
+        let descriptors: Arc<[MetricDescriptor]> = descriptors.into();
+        let connections = Arc::new(AtomicUsize::new(0));
+        thread::Builder::new()
+            .name("exporter".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("failed to accept connection: {}", err);
+                            continue;
+                        }
+                    };
+                    // Serve every connection from its own thread, so an idle or slow client
+                    // does not hold up scrapes
+                    if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                        warn!("too many open connections, closing new connection");
+                        continue;
+                    }
+                    let latest = latest.clone();
+                    let descriptors = descriptors.clone();
+                    let open = connections.clone();
+                    let spawned = thread::Builder::new()
+                        .name("exporter-conn".to_string())
+                        .spawn(move || {
+                            if let Err(err) = handle_connection(stream, &latest, &descriptors) {
+                                debug!("failed to serve scrape: {}", err);
+                            }
+                            open.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    if let Err(err) = spawned {
+                        warn!("failed to start connection thread: {}", err);
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                    }
+                }
+            })
+            .map_err(|err| miette!("failed to start exporter thread: {}", err))?;
+
+        Ok(exporter)
+    }
+
+    /// Replace the metrics served to scrapers.
+    pub fn update(&self, timeseries: Vec<TimeSeries>) {
+        *self.latest.lock().unwrap() = timeseries;
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    latest: &Mutex<Vec<TimeSeries>>,
+    descriptors: &[MetricDescriptor],
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers, the request body is never needed
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => {
+            let body = encode_text(&latest.lock().unwrap(), descriptors);
+            ("200 OK", TEXT_CONTENT_TYPE, body)
+        }
+        ("GET", "/") => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            "<html><body><a href=\"/metrics\">Metrics</a></body></html>\n".to_string(),
+        ),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+/// Render series in the Prometheus text exposition format, grouped by metric name.
+///
+/// Metrics without a descriptor are rendered as `untyped`.
+pub fn encode_text(timeseries: &[TimeSeries], descriptors: &[MetricDescriptor]) -> String {
+    // Group by metric name while keeping the order in which metrics were collected
+    let mut families: Vec<(&str, Vec<&TimeSeries>)> = vec![];
+    for series in timeseries {
+        let name = metric_name(series);
+        match families.iter_mut().find(|(family, _)| *family == name) {
+            Some((_, members)) => members.push(series),
+            None => families.push((name, vec![series])),
+        }
+    }
+
+    let mut out = String::new();
+    for (name, members) in families {
+        match descriptors
+            .iter()
+            .find(|descriptor| descriptor.name == name)
+        {
+            Some(descriptor) => {
+                let _ = writeln!(out, "# HELP {} {}", name, escape_help(descriptor.help));
+                let _ = writeln!(out, "# TYPE {} {}", name, descriptor.kind.as_str());
+            }
+            None => {
+                let _ = writeln!(out, "# TYPE {} untyped", name);
+            }
+        }
+
+        for series in members {
+            out.push_str(name);
+            let mut labels = series
+                .labels
+                .iter()
+                .filter(|label| label.name != LABEL_NAME)
+                .peekable();
+            if labels.peek().is_some() {
+                out.push('{');
+                for (i, label) in labels.enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    let _ = write!(
+                        out,
+                        "{}=\"{}\"",
+                        label.name,
+                        escape_label_value(&label.value)
+                    );
+                }
+                out.push('}');
+            }
+            let value = series
+                .samples
+                .last()
+                .map_or(f64::NAN, |sample| sample.value);
+            let _ = writeln!(out, " {}", format_value(value));
+        }
+    }
+    out
+}
+
+/// How `agemon once` and `--dry-run` print metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Prometheus text exposition format, as served on /metrics
+    #[default]
+    Prometheus,
+    /// One JSON object per series and line, with name, labels, value and timestamp
+    Json,
+}
+
+/// Render series in the given format, sorted by name and labels so outputs can be diffed.
+pub fn encode(
+    format: OutputFormat,
+    mut timeseries: Vec<TimeSeries>,
+    descriptors: &[MetricDescriptor],
+) -> String {
+    let pairs = |series: &TimeSeries| {
+        series
+            .labels
+            .iter()
+            .map(|label| (label.name.clone(), label.value.clone()))
+            .collect::<Vec<_>>()
+    };
+    timeseries.sort_by_cached_key(|series| (metric_name(series).to_string(), pairs(series)));
+    match format {
+        OutputFormat::Prometheus => encode_text(&timeseries, descriptors),
+        OutputFormat::Json => encode_json(&timeseries),
+    }
+}
+
+/// Render series as JSON lines, non-finite values are written as strings like in the
+/// Prometheus HTTP API.
+pub fn encode_json(timeseries: &[TimeSeries]) -> String {
+    let mut out = String::new();
+    for series in timeseries {
+        let labels: Map<String, Value> = series
+            .labels
+            .iter()
+            .filter(|label| label.name != LABEL_NAME)
+            .map(|label| (label.name.clone(), Value::from(label.value.as_str())))
+            .collect();
+        for sample in &series.samples {
+            let value = if sample.value.is_finite() {
+                json!(sample.value)
+            } else {
+                json!(format_value(sample.value))
+            };
+            let line = json!({
+                "name": metric_name(series),
+                "labels": labels,
+                "value": value,
+                "timestamp": sample.timestamp,
+            });
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+    out
+}
+
+fn metric_name(series: &TimeSeries) -> &str {
+    series
+        .labels
+        .iter()
+        .find(|label| label.name == LABEL_NAME)
+        .map_or("", |label| label.value.as_str())
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use prometheus_remote_write::{Label, Sample};
+
+    use super::*;
+    use crate::descriptors::{counter, gauge};
+
+    const DESCRIPTORS: &[MetricDescriptor] = &[
+        counter("test_requests_total", "", "Requests with \\ and\nnewline"),
+        gauge("test_temperature_celsius", "celsius", "Temperature"),
+    ];
+
+    fn series(name: &str, labels: &[(&str, &str)], value: f64) -> TimeSeries {
+        TimeSeries {
+            labels: [(LABEL_NAME, name)]
+                .iter()
+                .chain(labels)
+                .map(|(name, value)| Label {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            samples: vec![Sample {
+                value,
+                timestamp: 1000,
+            }],
+        }
+    }
+
+    #[test]
+    fn text_groups_by_metric_name() {
+        let timeseries = [
+            series("test_temperature_celsius", &[("sensor", "cpu")], 41.5),
+            series("test_requests_total", &[("code", "200")], 3.0),
+            series("test_temperature_celsius", &[("sensor", "gpu")], f64::NAN),
+            series("test_unknown", &[], f64::INFINITY),
+            series(
+                "test_requests_total",
+                &[("path", "a\"b\\c\nd")],
+                f64::NEG_INFINITY,
+            ),
+        ];
+        assert_eq!(
+            encode_text(&timeseries, DESCRIPTORS),
+            "# HELP test_temperature_celsius Temperature\n\
+             # TYPE test_temperature_celsius gauge\n\
+             test_temperature_celsius{sensor=\"cpu\"} 41.5\n\
+             test_temperature_celsius{sensor=\"gpu\"} NaN\n\
+             # HELP test_requests_total Requests with \\\\ and\\nnewline\n\
+             # TYPE test_requests_total counter\n\
+             test_requests_total{code=\"200\"} 3\n\
+             test_requests_total{path=\"a\\\"b\\\\c\\nd\"} -Inf\n\
+             # TYPE test_unknown untyped\n\
+             test_unknown +Inf\n"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let timeseries = [
+            series("test_requests_total", &[("code", "200")], 3.0),
+            series("test_temperature_celsius", &[], f64::NAN),
+        ];
+        assert_eq!(
+            encode_json(&timeseries),
+            "{\"labels\":{\"code\":\"200\"},\"name\":\"test_requests_total\",\
+             \"timestamp\":1000,\"value\":3.0}\n\
+             {\"labels\":{},\"name\":\"test_temperature_celsius\",\
+             \"timestamp\":1000,\"value\":\"NaN\"}\n"
+        );
+    }
+
+    /// Send `request` to a connection served by `handle_connection`, returns the response.
+    fn serve(request: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let latest = Mutex::new(vec![series("test_requests_total", &[], 1.0)]);
+        let server = thread::spawn(move || handle_connection(stream, &latest, DESCRIPTORS));
+
+        client.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap().unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_metrics() {
+        let response = serve("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Type: {}\r\n", TEXT_CONTENT_TYPE)));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(body.ends_with("test_requests_total 1\n"));
+
+        let response = serve("POST /metrics HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        let response = serve("GET /other HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,59 @@
+//! System metrics agent pushing to Prometheus remote write.
+//!
+//! The `agemon` binary is a thin wrapper around this crate. Other binaries can reuse the same
+//! pipeline with their own collectors:
+//!
+//! ```no_run
+//! use agemon::descriptors::{MetricDescriptor, gauge};
+//! use agemon::{Agent, Args, Collector, Sink, collectors};
+//!
+//! const QUEUE_METRICS: &[MetricDescriptor] =
+//!     &[gauge("myapp_queue_depth", "", "Jobs waiting in the queue")];
+//!
+//! struct Queue;
+//!
+//! impl Collector for Queue {
+//!     fn name(&self) -> &str {
+//!         "queue"
+//!     }
+//!
+//!     fn descriptors(&self) -> &'static [MetricDescriptor] {
+//!         QUEUE_METRICS
+//!     }
+//!
+//!     fn collect(&mut self, sink: &mut Sink) {
+//!         sink.push("myapp_queue_depth", 42.0);
+//!     }
+//! }
+//!
+//! fn main() -> miette::Result<()> {
+//!     let args = Args::load()?;
+//!     let mut registry =
+//!         collectors::builtin(&args.collectors, args.top_processes, &args.host_paths());
+//!     registry.register(Queue);
+//!     Agent::new(args, registry)?.run()
+//! }
+//! ```
+
+mod agent;
+mod args;
+mod auth;
+pub mod buffer;
+mod collector;
+pub mod collectors;
+pub mod config;
+pub mod descriptors;
+mod endpoint;
+pub mod exporter;
+mod notify;
+mod proto;
+mod relabel;
+mod remote_write;
+pub mod retry;
+mod shutdown;
+mod staleness;
+mod tls;
+
+pub use agent::Agent;
+pub use args::{Args, Command};
+pub use collector::{Collector, Registry, Sink};
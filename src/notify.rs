@@ -0,0 +1,141 @@
+use std::{env, ffi::OsString, process, time::Duration};
+
+use tracing::{debug, warn};
+
+/// Reports readiness, status and watchdog pings to systemd through `$NOTIFY_SOCKET`, for
+/// services with `Type=notify` and `WatchdogSec`.
+///
+/// Does nothing when not started by systemd.
+#[derive(Debug, Default)]
+pub(crate) struct Notifier {
+    #[cfg(unix)]
+    socket: Option<std::os::unix::net::SocketAddr>,
+    watchdog_timeout: Option<Duration>,
+    status: String,
+}
+
+impl Notifier {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var_os(name))
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<OsString>) -> Self {
+        let var_str = |name| var(name).and_then(|value| value.into_string().ok());
+        // The watchdog is meant for the process systemd started, not one inheriting its env
+        let watched = var_str("WATCHDOG_PID").is_none_or(|pid| pid == process::id().to_string());
+        let watchdog_timeout = var_str("WATCHDOG_USEC")
+            .and_then(|usec| usec.parse().ok())
+            .filter(|&usec| usec > 0 && watched)
+            .map(Duration::from_micros);
+
+        Notifier {
+            #[cfg(unix)]
+            socket: var("NOTIFY_SOCKET").and_then(|path| socket_addr(&path)),
+            watchdog_timeout,
+            status: String::new(),
+        }
+    }
+
+    /// How long systemd waits for a watchdog ping before restarting the service.
+    pub fn watchdog_timeout(&self) -> Option<Duration> {
+        self.watchdog_timeout
+    }
+
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    pub fn watchdog(&self) {
+        self.send("WATCHDOG=1");
+    }
+
+    /// Show this in `systemctl status`, only sent when it changed.
+    pub fn status(&mut self, status: String) {
+        if status != self.status {
+            self.send(&format!("STATUS={}", status));
+            self.status = status;
+        }
+    }
+
+    #[cfg(unix)]
+    fn send(&self, message: &str) {
+        use std::os::unix::net::UnixDatagram;
+
+        let Some(addr) = &self.socket else {
+            return;
+        };
+        debug!("notifying systemd: {}", message);
+        if let Err(err) =
+            UnixDatagram::unbound().and_then(|socket| socket.send_to_addr(message.as_bytes(), addr))
+        {
+            warn!("failed to notify systemd: {}", err);
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, _message: &str) {}
+}
+
+/// A socket path, or an abstract socket name on Linux if it starts with `@`.
+#[cfg(unix)]
+fn socket_addr(path: &std::ffi::OsStr) -> Option<std::os::unix::net::SocketAddr> {
+    use std::os::unix::{ffi::OsStrExt, net::SocketAddr};
+
+    let result = match path.as_bytes() {
+        #[cfg(target_os = "linux")]
+        [b'@', name @ ..] => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name)
+        }
+        _ => SocketAddr::from_pathname(path),
+    };
+    result
+        .map_err(|err| warn!("invalid NOTIFY_SOCKET {:?}: {}", path, err))
+        .ok()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{collections::HashMap, os::unix::net::UnixDatagram};
+
+    use super::*;
+
+    fn recv(socket: &UnixDatagram) -> String {
+        let mut buf = [0; 256];
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn notifies_systemd_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        // Setting the real environment would race with other tests reading it
+        let vars = HashMap::from([
+            ("NOTIFY_SOCKET", OsString::from(&path)),
+            ("WATCHDOG_USEC", OsString::from("300000000")),
+            ("WATCHDOG_PID", OsString::from(process::id().to_string())),
+        ]);
+        let mut notifier = Notifier::from_vars(|name| vars.get(name).cloned());
+        assert_eq!(notifier.watchdog_timeout(), Some(Duration::from_secs(300)));
+
+        notifier.ready();
+        assert_eq!(recv(&socket), "READY=1");
+        notifier.status("default: pushed".to_string());
+        assert_eq!(recv(&socket), "STATUS=default: pushed");
+        // An unchanged status is not sent again
+        notifier.status("default: pushed".to_string());
+        notifier.watchdog();
+        assert_eq!(recv(&socket), "WATCHDOG=1");
+        notifier.stopping();
+        assert_eq!(recv(&socket), "STOPPING=1");
+    }
+}
@@ -0,0 +1,320 @@
+use prometheus_remote_write::{LABEL_NAME, Label, TimeSeries};
+
+use crate::config::{RelabelAction, RelabelConfig, is_valid_label_name};
+
+/// Apply the relabeling rules in order to every series, dropping series that a rule discards or
+/// that are left without a metric name.
+pub(crate) fn relabel(timeseries: Vec<TimeSeries>, configs: &[RelabelConfig]) -> Vec<TimeSeries> {
+    if configs.is_empty() {
+        return timeseries;
+    }
+    timeseries
+        .into_iter()
+        .filter_map(|mut series| {
+            for config in configs {
+                if !apply(config, &mut series.labels) {
+                    return None;
+                }
+            }
+            let named = series
+                .labels
+                .iter()
+                .any(|label| label.name == LABEL_NAME && !label.value.is_empty());
+            named.then_some(series)
+        })
+        .collect()
+}
+
+/// Apply one rule to the labels of a series, returns false if the series should be dropped.
+fn apply(config: &RelabelConfig, labels: &mut Vec<Label>) -> bool {
+    let source = config
+        .source_labels
+        .iter()
+        .map(|name| label_value(labels, name))
+        .collect::<Vec<_>>()
+        .join(&config.separator);
+
+    match config.action {
+        RelabelAction::Keep => return config.regex.is_match(&source),
+        RelabelAction::Drop => return !config.regex.is_match(&source),
+        RelabelAction::Replace => {
+            let Some(captures) = config.regex.captures(&source) else {
+                return true;
+            };
+            let mut target = String::new();
+            captures.expand(
+                config.target_label.as_deref().unwrap_or_default(),
+                &mut target,
+            );
+            if !is_valid_label_name(&target) {
+                return true;
+            }
+            let mut value = String::new();
+            captures.expand(&config.replacement, &mut value);
+            set_label(labels, &target, value);
+        }
+        RelabelAction::Hashmod => {
+            // Same bucket as Prometheus: the last 8 bytes of the MD5 as a big-endian integer
+            let hash = md5::compute(&source);
+            let hash = u64::from_be_bytes(hash.0[8..].try_into().unwrap());
+            let bucket = hash % config.modulus.unwrap_or(1);
+            set_label(
+                labels,
+                config.target_label.as_deref().unwrap_or_default(),
+                bucket.to_string(),
+            );
+        }
+        RelabelAction::Labeldrop => labels.retain(|label| !config.regex.is_match(&label.name)),
+        RelabelAction::Labelkeep => labels.retain(|label| config.regex.is_match(&label.name)),
+    }
+    true
+}
+
+fn label_value<'a>(labels: &'a [Label], name: &str) -> &'a str {
+    labels
+        .iter()
+        .find(|label| label.name == name)
+        .map_or("", |label| label.value.as_str())
+}
+
+/// Set a label, an empty value removes it as in Prometheus.
+fn set_label(labels: &mut Vec<Label>, name: &str, value: String) {
+    labels.retain(|label| label.name != name);
+    if !value.is_empty() {
+        labels.push(Label {
+            name: name.to_string(),
+            value,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    type Labels = Vec<(String, String)>;
+
+    #[derive(Deserialize)]
+    struct Rules {
+        metric_relabel_configs: Vec<RelabelConfig>,
+    }
+
+    fn series(labels: &[(&str, &str)]) -> TimeSeries {
+        TimeSeries {
+            labels: labels
+                .iter()
+                .map(|(name, value)| Label {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            samples: vec![],
+        }
+    }
+
+    /// Relabel one series with the rules in `toml`, returns its sorted labels if it is kept.
+    fn run(toml: &str, labels: &[(&str, &str)]) -> Option<Labels> {
+        let rules = toml::from_str::<Rules>(toml)
+            .unwrap()
+            .metric_relabel_configs;
+        let mut kept = relabel(vec![series(labels)], &rules);
+        assert!(kept.len() <= 1);
+        let series = kept.pop()?;
+        let mut labels = series
+            .labels
+            .into_iter()
+            .map(|label| (label.name, label.value))
+            .collect::<Vec<_>>();
+        labels.sort();
+        Some(labels)
+    }
+
+    fn labels(labels: &[(&str, &str)]) -> Option<Labels> {
+        let mut labels = labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        labels.sort();
+        Some(labels)
+    }
+
+    const INPUT: &[(&str, &str)] = &[
+        ("__name__", "node_filesystem_avail_bytes"),
+        ("device", "/dev/sda1"),
+        ("mountpoint", "/boot"),
+    ];
+
+    #[test]
+    fn actions() {
+        let cases: &[(&str, &str, Option<Labels>)] = &[
+            (
+                "keep matching",
+                r#"source_labels = ["mountpoint"]
+                regex = "/boot"
+                action = "keep""#,
+                labels(INPUT),
+            ),
+            (
+                "keep not matching",
+                r#"source_labels = ["mountpoint"]
+                regex = "/"
+                action = "keep""#,
+                None,
+            ),
+            (
+                "drop matching",
+                r#"source_labels = ["__name__", "mountpoint"]
+                regex = "node_filesystem_.*;/boot"
+                action = "drop""#,
+                None,
+            ),
+            (
+                "drop not matching",
+                r#"source_labels = ["device"]
+                regex = "tmpfs"
+                action = "drop""#,
+                labels(INPUT),
+            ),
+            (
+                "replace with capture group",
+                r#"source_labels = ["device"]
+                regex = "/dev/(.+)"
+                target_label = "disk"
+                replacement = "$1""#,
+                labels(&[
+                    ("__name__", "node_filesystem_avail_bytes"),
+                    ("device", "/dev/sda1"),
+                    ("disk", "sda1"),
+                    ("mountpoint", "/boot"),
+                ]),
+            ),
+            (
+                "replace with capture group in the target label",
+                r#"source_labels = ["mountpoint"]
+                regex = "/(.+)"
+                target_label = "mount_$1"
+                replacement = "yes""#,
+                labels(&[
+                    ("__name__", "node_filesystem_avail_bytes"),
+                    ("device", "/dev/sda1"),
+                    ("mount_boot", "yes"),
+                    ("mountpoint", "/boot"),
+                ]),
+            ),
+            (
+                "replace not matching leaves the labels",
+                r#"source_labels = ["device"]
+                regex = "/dev/nvme(.+)"
+                target_label = "disk""#,
+                labels(INPUT),
+            ),
+            (
+                "replace with an empty value deletes the label",
+                r#"source_labels = ["device"]
+                regex = ".*"
+                target_label = "mountpoint"
+                replacement = """#,
+                labels(&[
+                    ("__name__", "node_filesystem_avail_bytes"),
+                    ("device", "/dev/sda1"),
+                ]),
+            ),
+            (
+                "regex is anchored",
+                r#"source_labels = ["device"]
+                regex = "sda1"
+                target_label = "disk""#,
+                labels(INPUT),
+            ),
+            (
+                "labeldrop",
+                r#"regex = "device|mount.*"
+                action = "labeldrop""#,
+                labels(&[("__name__", "node_filesystem_avail_bytes")]),
+            ),
+            (
+                "labelkeep",
+                r#"regex = "__name__|device"
+                action = "labelkeep""#,
+                labels(&[
+                    ("__name__", "node_filesystem_avail_bytes"),
+                    ("device", "/dev/sda1"),
+                ]),
+            ),
+            (
+                "dropping the metric name drops the series",
+                r#"regex = "__name__"
+                action = "labeldrop""#,
+                None,
+            ),
+        ];
+
+        for (name, rule, expected) in cases {
+            let toml = format!("[[metric_relabel_configs]]\n{}", rule);
+            assert_eq!(&run(&toml, INPUT), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn rules_apply_in_order() {
+        let toml = r#"
+            [[metric_relabel_configs]]
+            source_labels = ["device"]
+            regex = "/dev/(.+)"
+            target_label = "disk"
+
+            [[metric_relabel_configs]]
+            regex = "device"
+            action = "labeldrop"
+
+            [[metric_relabel_configs]]
+            source_labels = ["disk"]
+            regex = "sda.*"
+            action = "keep"
+        "#;
+        assert_eq!(
+            run(toml, INPUT),
+            labels(&[
+                ("__name__", "node_filesystem_avail_bytes"),
+                ("disk", "sda1"),
+                ("mountpoint", "/boot"),
+            ])
+        );
+    }
+
+    #[test]
+    fn hashmod_matches_prometheus() {
+        // Same input and bucket as the hashmod case in Prometheus' relabel tests
+        let toml = r#"
+            [[metric_relabel_configs]]
+            source_labels = ["c"]
+            target_label = "d"
+            modulus = 1000
+            action = "hashmod"
+        "#;
+        let input = [("__name__", "up"), ("a", "foo"), ("b", "bar"), ("c", "baz")];
+        assert_eq!(
+            run(toml, &input),
+            labels(&[
+                ("__name__", "up"),
+                ("a", "foo"),
+                ("b", "bar"),
+                ("c", "baz"),
+                ("d", "976"),
+            ])
+        );
+
+        // Source labels are joined with the separator before hashing
+        let toml = r#"
+            [[metric_relabel_configs]]
+            source_labels = ["a", "b"]
+            target_label = "shard"
+            modulus = 4
+            action = "hashmod"
+        "#;
+        let shard = run(toml, &input).unwrap();
+        assert!(shard.contains(&("shard".to_string(), "2".to_string())));
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant, SystemTime},
+};
+
+use miette::Result;
+use prometheus_remote_write::{HEADER_NAME_REMOTE_WRITE_VERSION, REMOTE_WRITE_VERSION_01};
+use reqwest::{
+    StatusCode, Url,
+    blocking::{Client, RequestBuilder},
+    header::{self, CONTENT_ENCODING, CONTENT_TYPE, HOST, HeaderMap, RETRY_AFTER},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    auth::RequestHeaders,
+    config::{RemoteWriteConfig, RemoteWriteProtocol},
+    proto::{WriteRequest, v2},
+    retry::{PushError, RetryPolicy, parse_retry_after},
+    shutdown::Shutdown,
+    tls::request_target,
+};
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Outcome of every HTTP request made to an endpoint, including retries and buffer replays.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PushStats {
+    pub attempts: u64,
+    /// Failed requests by HTTP status code, or `error` if no response was received
+    pub failures: BTreeMap<String, u64>,
+    /// Compressed request bodies the endpoint responded to
+    pub bytes_sent: u64,
+    pub last_success: Option<SystemTime>,
+    pub last_duration: Option<Duration>,
+}
+
+pub(crate) fn push_metrics(
+    client: &Client,
+    config: &RemoteWriteConfig,
+    headers: &mut RequestHeaders,
+    policy: &RetryPolicy,
+    stats: &mut PushStats,
+    shutdown: &Shutdown,
+    write_request: WriteRequest,
+) -> Result<(), PushError> {
+    let url = config
+        .url
+        .parse::<Url>()
+        .map_err(|err| PushError::Permanent(format!("invalid remote write url: {}", err)))?;
+    let (url, host) = match request_target(&config.tls, &url) {
+        Some((target, host)) => (target, Some(host)),
+        None => (url, None),
+    };
+    let samples = write_request
+        .timeseries
+        .iter()
+        .map(|series| series.samples.len())
+        .sum();
+    let (body, content_type, version) = match config.protocol {
+        RemoteWriteProtocol::V1 => (
+            write_request.encode_compressed(),
+            prometheus_remote_write::CONTENT_TYPE,
+            REMOTE_WRITE_VERSION_01,
+        ),
+        RemoteWriteProtocol::V2 => (
+            v2::Request::new(write_request.timeseries, &write_request.metadata).encode_compressed(),
+            v2::CONTENT_TYPE,
+            v2::REMOTE_WRITE_VERSION,
+        ),
+    };
+    let body =
+        body.map_err(|err| PushError::Permanent(format!("failed to encode request: {}", err)))?;
+
+    let mut retry = 0;
+    loop {
+        // Headers are rebuilt for every attempt to pick up a rotated token
+        let result = headers.get().and_then(|headers| {
+            let mut req_builder = client.post(url.clone()).headers(headers);
+            if let Some(host) = &host {
+                req_builder = req_builder.header(HOST, host);
+            }
+            let req_builder = req_builder
+                .header(CONTENT_TYPE, content_type)
+                .header(CONTENT_ENCODING, "snappy")
+                .header(HEADER_NAME_REMOTE_WRITE_VERSION, version)
+                .header(header::USER_AGENT, USER_AGENT)
+                .body(body.clone());
+            stats.attempts += 1;
+            let start = Instant::now();
+            let result = send_request(req_builder, body.len(), config.protocol, samples, stats);
+            stats.last_duration = Some(start.elapsed());
+            result
+        });
+        let err = match result {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        let PushError::Retryable { retry_after, .. } = &err else {
+            return Err(err);
+        };
+
+        retry += 1;
+        let Some(delay) = policy.delay(retry, *retry_after) else {
+            return Err(err);
+        };
+        warn!(
+            "{}, retrying in {:?} ({}/{})",
+            err, delay, retry, policy.max_retries
+        );
+        // Retries would outlast the shutdown deadline, leave the batch to the buffer
+        if shutdown.sleep(delay) {
+            return Err(err);
+        }
+    }
+}
+
+fn send_request(
+    req_builder: RequestBuilder,
+    body_len: usize,
+    protocol: RemoteWriteProtocol,
+    samples: usize,
+    stats: &mut PushStats,
+) -> Result<(), PushError> {
+    let response = req_builder.send().map_err(|err| {
+        *stats.failures.entry("error".to_string()).or_default() += 1;
+        if err.is_builder() {
+            PushError::Permanent(err.to_string())
+        } else {
+            PushError::Retryable {
+                reason: err.to_string(),
+                retry_after: None,
+            }
+        }
+    })?;
+    stats.bytes_sent += body_len as u64;
+    let status = response.status();
+    debug!("push response status: {}", status);
+
+    if status.is_success() {
+        stats.last_success = Some(SystemTime::now());
+        if protocol == RemoteWriteProtocol::V2 {
+            check_samples_written(response.headers(), samples);
+        }
+        return Ok(());
+    }
+    *stats
+        .failures
+        .entry(status.as_str().to_string())
+        .or_default() += 1;
+    if status == StatusCode::UNSUPPORTED_MEDIA_TYPE && protocol == RemoteWriteProtocol::V2 {
+        return Err(PushError::Permanent(format!(
+            "push failed with status: {}: endpoint does not support remote write 2.0, use \
+             --remote-write-protocol v1",
+            status
+        )));
+    }
+
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response.text().unwrap_or_default();
+    Err(PushError::from_status(status, &body, retry_after))
+}
+
+/// Remote write 2.0 receivers report how many samples they stored, warn about partial writes.
+fn check_samples_written(headers: &HeaderMap, samples: usize) {
+    let Some(written) = headers.get(v2::HEADER_SAMPLES_WRITTEN) else {
+        debug!("endpoint did not report written samples, it may only support remote write 1.0");
+        return;
+    };
+    match written
+        .to_str()
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+    {
+        Some(written) if written < samples => {
+            warn!("endpoint only wrote {} of {} samples", written, samples);
+        }
+        Some(written) => debug!("endpoint wrote {} samples", written),
+        None => debug!(
+            "invalid {} header: {:?}",
+            v2::HEADER_SAMPLES_WRITTEN,
+            written
+        ),
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fmt,
+    hash::{BuildHasher, Hasher, RandomState},
+    time::{Duration, SystemTime},
+};
+
+use reqwest::StatusCode;
+
+/// Why a push failed, deciding whether the batch is worth sending again.
+#[derive(Debug)]
+pub enum PushError {
+    /// Connection failure, 5xx or 429: the same batch may be accepted later
+    Retryable {
+        reason: String,
+        retry_after: Option<Duration>,
+    },
+    /// Any other non-2xx response: the endpoint will never accept this batch as-is
+    Permanent(String),
+}
+
+impl PushError {
+    /// Classify a non-2xx remote-write response as described by the remote write spec.
+    pub fn from_status(status: StatusCode, body: &str, retry_after: Option<Duration>) -> Self {
+        let reason = match body.trim() {
+            "" => format!("push failed with status: {}", status),
+            body => format!("push failed with status: {}: {}", status, body),
+        };
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            PushError::Retryable {
+                reason,
+                retry_after,
+            }
+        } else {
+            PushError::Permanent(reason)
+        }
+    }
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Retryable { reason, .. } | PushError::Permanent(reason) => {
+                f.write_str(reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PushError {}
+
+impl miette::Diagnostic for PushError {}
+
+/// Exponential backoff with jitter between attempts of the same push.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry (starting at 1), or `None` once retries are exhausted or the
+    /// server asked us to wait longer than `max_backoff`.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if retry > self.max_retries {
+            return None;
+        }
+
+        let exponential = self
+            .min_backoff
+            .saturating_mul(1 << retry.saturating_sub(1).min(16))
+            .min(self.max_backoff);
+        // Equal jitter: keep half of the backoff, randomize the other half
+        let half = exponential / 2;
+        let backoff = half + half.mul_f64(random_fraction());
+
+        let delay = backoff.max(retry_after.unwrap_or_default());
+        (delay <= self.max_backoff).then_some(delay)
+    }
+}
+
+/// Parse a `Retry-After` header, given either as delay-seconds or as an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+fn random_fraction() -> f64 {
+    // RandomState is seeded per instance, which is plenty for spreading retries
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 6,
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap_with_equal_jitter() {
+        let policy = policy();
+        for (retry, expected_ms) in [
+            (1, 500),
+            (2, 1000),
+            (3, 2000),
+            (4, 4000),
+            (5, 8000),
+            (6, 10000),
+        ] {
+            let expected = Duration::from_millis(expected_ms);
+            for _ in 0..100 {
+                let delay = policy.delay(retry, None).unwrap();
+                assert!(
+                    delay >= expected / 2 && delay <= expected,
+                    "retry {retry}: {delay:?} not within [{:?}, {expected:?}]",
+                    expected / 2
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        assert_eq!(policy().delay(7, None), None);
+        let no_retries = RetryPolicy {
+            max_retries: 0,
+            ..policy()
+        };
+        assert_eq!(no_retries.delay(1, None), None);
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let policy = policy();
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(5))),
+            Some(Duration::from_secs(5))
+        );
+        // Waiting longer than the backoff cap is not worth holding up the queue
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(60))), None);
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        assert_eq!(parse_retry_after(" 7 "), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Thu, 01 Jan 1970 00:00:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(120));
+        let delay = parse_retry_after(&date).unwrap();
+        assert!(delay > Duration::from_secs(110) && delay <= Duration::from_secs(120));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn classifies_status_codes() {
+        for (status, retryable) in [
+            (StatusCode::TOO_MANY_REQUESTS, true),
+            (StatusCode::INTERNAL_SERVER_ERROR, true),
+            (StatusCode::BAD_GATEWAY, true),
+            (StatusCode::SERVICE_UNAVAILABLE, true),
+            (StatusCode::BAD_REQUEST, false),
+            (StatusCode::UNAUTHORIZED, false),
+            (StatusCode::NOT_FOUND, false),
+            (StatusCode::PAYLOAD_TOO_LARGE, false),
+        ] {
+            let err = PushError::from_status(status, "", None);
+            assert_eq!(
+                matches!(err, PushError::Retryable { .. }),
+                retryable,
+                "{status}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_retry_after_and_body() {
+        let err = PushError::from_status(
+            StatusCode::TOO_MANY_REQUESTS,
+            " slow down\n",
+            Some(Duration::from_secs(3)),
+        );
+        assert_eq!(
+            err.to_string(),
+            "push failed with status: 429 Too Many Requests: slow down"
+        );
+        let PushError::Retryable { retry_after, .. } = err else {
+            panic!("429 must be retryable");
+        };
+        assert_eq!(retry_after, Some(Duration::from_secs(3)));
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use miette::Result;
+
+/// Set once the agent is asked to stop, waking every thread sleeping on it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Shutdown(Arc<(Mutex<bool>, Condvar)>);
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn trigger(&self) {
+        let (triggered, condvar) = &*self.0;
+        *triggered.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.0.0.lock().unwrap()
+    }
+
+    /// Sleep for `duration` unless shutdown is triggered first, returns whether it was.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let (triggered, condvar) = &*self.0;
+        let mut triggered = triggered.lock().unwrap();
+        while !*triggered {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            triggered = condvar.wait_timeout(triggered, deadline - now).unwrap().0;
+        }
+        *triggered
+    }
+
+    /// Trigger on SIGTERM or SIGINT, a second signal exits right away.
+    #[cfg(unix)]
+    pub fn on_signals(&self) -> Result<()> {
+        use signal_hook::{
+            consts::{SIGINT, SIGTERM},
+            iterator::Signals,
+        };
+
+        let mut signals = Signals::new([SIGTERM, SIGINT])
+            .map_err(|err| miette::miette!("failed to install signal handlers: {}", err))?;
+        let shutdown = self.clone();
+        std::thread::Builder::new()
+            .name("signals".to_string())
+            .spawn(move || {
+                for signal in &mut signals {
+                    if shutdown.is_triggered() {
+                        tracing::warn!("received signal {} again, exiting now", signal);
+                        std::process::exit(128 + signal);
+                    }
+                    tracing::info!("received signal {}, shutting down", signal);
+                    shutdown.trigger();
+                }
+            })
+            .map_err(|err| miette::miette!("failed to start signal thread: {}", err))?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn on_signals(&self) -> Result<()> {
+        Ok(())
+    }
+}
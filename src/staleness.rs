@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use prometheus_remote_write::{Label, Sample, TimeSeries};
+
+/// Bit pattern of the NaN Prometheus uses to mark a series as stale.
+pub const STALE_NAN_BITS: u64 = 0x7ff0000000000002;
+
+/// Remembers the series pushed in the previous run of every source to mark the ones that vanished
+/// as stale, instead of leaving Prometheus to show their last value for the lookback period.
+///
+/// Sources are tracked separately since collectors on longer intervals do not run every cycle.
+#[derive(Debug, Default)]
+pub struct StalenessTracker {
+    previous: HashMap<String, HashSet<Vec<(String, String)>>>,
+}
+
+impl StalenessTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the series of the current run of `source` and return staleness markers at
+    /// `timestamp` for every series of its previous run that is missing from it.
+    pub fn update(
+        &mut self,
+        source: &str,
+        timeseries: &[TimeSeries],
+        timestamp: i64,
+    ) -> Vec<TimeSeries> {
+        let current: HashSet<_> = timeseries.iter().map(label_set).collect();
+        let previous = self.previous.entry(source.to_string()).or_default();
+        let mut vanished: Vec<_> = previous.difference(&current).collect();
+        // Keep the output stable regardless of hash order
+        vanished.sort();
+        let markers = vanished
+            .into_iter()
+            .map(|labels| TimeSeries {
+                labels: labels
+                    .iter()
+                    .map(|(name, value)| Label {
+                        name: name.clone(),
+                        value: value.clone(),
+                    })
+                    .collect(),
+                samples: vec![Sample {
+                    value: f64::from_bits(STALE_NAN_BITS),
+                    timestamp,
+                }],
+            })
+            .collect();
+        *previous = current;
+        markers
+    }
+}
+
+fn label_set(series: &TimeSeries) -> Vec<(String, String)> {
+    series
+        .labels
+        .iter()
+        .map(|label| (label.name.clone(), label.value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(name: &str, cpu: &str) -> TimeSeries {
+        TimeSeries {
+            labels: vec![
+                Label {
+                    name: "__name__".to_string(),
+                    value: name.to_string(),
+                },
+                Label {
+                    name: "cpu".to_string(),
+                    value: cpu.to_string(),
+                },
+            ],
+            samples: vec![Sample {
+                value: 1.0,
+                timestamp: 0,
+            }],
+        }
+    }
+
+    #[test]
+    fn marks_vanished_series_once() {
+        let mut tracker = StalenessTracker::new();
+        let first = [series("cpu_seconds", "0"), series("cpu_seconds", "1")];
+        assert!(tracker.update("cpu", &first, 1000).is_empty());
+
+        let markers = tracker.update("cpu", &first[..1], 2000);
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].labels, first[1].labels);
+        assert_eq!(markers[0].samples.len(), 1);
+        assert_eq!(markers[0].samples[0].timestamp, 2000);
+        assert_eq!(markers[0].samples[0].value.to_bits(), STALE_NAN_BITS);
+
+        // Already marked, not marked again
+        assert!(tracker.update("cpu", &first[..1], 3000).is_empty());
+    }
+
+    #[test]
+    fn returning_series_is_not_marked() {
+        let mut tracker = StalenessTracker::new();
+        let all = [series("cpu_seconds", "0"), series("cpu_seconds", "1")];
+        tracker.update("cpu", &all, 1000);
+        assert_eq!(tracker.update("cpu", &all[..1], 2000).len(), 1);
+        assert!(tracker.update("cpu", &all, 3000).is_empty());
+        assert!(tracker.update("cpu", &all, 4000).is_empty());
+    }
+
+    #[test]
+    fn sources_are_tracked_separately() {
+        let mut tracker = StalenessTracker::new();
+        let cpu = [series("cpu_seconds", "0")];
+        let disk = [series("disk_bytes", "")];
+        tracker.update("cpu", &cpu, 1000);
+        tracker.update("disk", &disk, 1000);
+
+        // The disk collector skips this cycle, its series are not stale
+        assert!(tracker.update("cpu", &cpu, 2000).is_empty());
+        // and an empty run of one source only marks its own series
+        let markers = tracker.update("cpu", &[], 3000);
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].labels, cpu[0].labels);
+        assert!(tracker.update("disk", &disk, 3000).is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    fs,
+    net::ToSocketAddrs,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use miette::{IntoDiagnostic, Result, miette};
+use reqwest::{
+    Url,
+    blocking::Client,
+    dns::{Addrs, Name, Resolve, Resolving},
+};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, Error, RootCertStore, SignatureScheme,
+    client::{
+        WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime, pem::PemObject},
+};
+use tracing::{info, warn};
+
+use crate::config::TlsConfig;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP client of an endpoint, rebuilt when one of its certificate files changes so rotated
+/// certificates are picked up without a restart.
+pub(crate) struct HttpClient {
+    tls: TlsConfig,
+    url: Option<Url>,
+    modified: Vec<Option<SystemTime>>,
+    client: Client,
+}
+
+impl HttpClient {
+    pub fn new(endpoint: &str, url: &str, tls: TlsConfig) -> Result<Self> {
+        // An invalid URL fails every push with a clear error, nothing to do here
+        let url = url.parse::<Url>().ok();
+        let modified = modified_times(&tls);
+        let client =
+            build_client(&tls, url.as_ref()).map_err(|err| miette!("{}: {}", endpoint, err))?;
+        Ok(HttpClient {
+            tls,
+            url,
+            modified,
+            client,
+        })
+    }
+
+    /// The client for the next push, reloading the certificate files first if they changed.
+    ///
+    /// Files that cannot be loaded, e.g. a certificate already replaced while its key is not
+    /// yet, keep the previous client until the next push tries again.
+    pub fn get(&mut self) -> &Client {
+        let modified = modified_times(&self.tls);
+        if modified != self.modified {
+            match build_client(&self.tls, self.url.as_ref()) {
+                Ok(client) => {
+                    info!("reloaded TLS certificates");
+                    self.client = client;
+                    self.modified = modified;
+                }
+                Err(err) => warn!("failed to reload TLS certificates: {}", err),
+            }
+        }
+        &self.client
+    }
+}
+
+fn modified_times(tls: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [&tls.ca_file, &tls.cert_file, &tls.key_file]
+        .into_iter()
+        .flatten()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Where to send requests for `url`: with a TLS server name on an `https` URL, to the server
+/// name instead of the URL host, so rustls sends it as SNI, with a `Host` header still naming
+/// the URL host. [`ServerNameResolver`] connects to the URL host's address.
+pub(crate) fn request_target(tls: &TlsConfig, url: &Url) -> Option<(Url, String)> {
+    let server_name = tls.server_name.as_deref()?;
+    if url.scheme() != "https" {
+        return None;
+    }
+    let mut host = url.host_str()?.to_string();
+    if let Some(port) = url.port() {
+        host = format!("{}:{}", host, port);
+    }
+    let mut target = url.clone();
+    target.set_host(Some(server_name)).ok()?;
+    Some((target, host))
+}
+
+fn build_client(tls: &TlsConfig, url: Option<&Url>) -> Result<Client> {
+    let mut builder = Client::builder().timeout(TIMEOUT);
+    if *tls == TlsConfig::default() {
+        return builder.build().into_diagnostic();
+    }
+    if let (Some(server_name), Some(url)) = (&tls.server_name, url)
+        && url.scheme() == "https"
+        && let Some(host) = url.host_str()
+    {
+        // IPv6 hosts come in brackets
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        builder = builder.dns_resolver(Arc::new(ServerNameResolver {
+            server_name: server_name.to_ascii_lowercase(),
+            host,
+        }));
+    }
+    builder
+        .use_preconfigured_tls(client_config(tls)?)
+        .build()
+        .into_diagnostic()
+}
+
+fn client_config(tls: &TlsConfig) -> Result<ClientConfig> {
+    let provider = Arc::new(ring::default_provider());
+
+    let mut roots = RootCertStore::empty();
+    match &tls.ca_file {
+        Some(path) => {
+            for cert in read_certs(path)? {
+                roots.add(cert).map_err(|err| {
+                    miette!("invalid CA certificate in {}: {}", path.display(), err)
+                })?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .into_diagnostic()?;
+    let server_name = tls
+        .server_name
+        .as_deref()
+        .map(|name| {
+            ServerName::try_from(name.to_string())
+                .map_err(|_| miette!("invalid TLS server name {:?}", name))
+        })
+        .transpose()?;
+    let verifier = Verifier {
+        webpki,
+        server_name,
+        insecure_skip_verify: tls.insecure_skip_verify,
+    };
+
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .into_diagnostic()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier));
+    match (&tls.cert_file, &tls.key_file) {
+        (Some(cert_file), Some(key_file)) => {
+            let key = PrivateKeyDer::from_pem_file(key_file)
+                .map_err(|err| miette!("failed to read TLS key {}: {}", key_file.display(), err))?;
+            builder
+                .with_client_auth_cert(read_certs(cert_file)?, key)
+                .map_err(|err| miette!("invalid client certificate: {}", err))
+        }
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => Err(miette!(
+            "a TLS client certificate needs both a cert_file and a key_file"
+        )),
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let error = |err| {
+        miette!(
+            "failed to read certificates from {}: {}",
+            path.display(),
+            err
+        )
+    };
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+    if certs.is_empty() {
+        return Err(miette!("no certificates found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+/// Resolves the TLS server name to the addresses of the URL host, see [`request_target`].
+struct ServerNameResolver {
+    server_name: String,
+    host: String,
+}
+
+impl Resolve for ServerNameResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = if name.as_str().eq_ignore_ascii_case(&self.server_name) {
+            self.host.as_str()
+        } else {
+            name.as_str()
+        };
+        // The blocking client waits for the lookup anyway. The port is replaced by the URL's.
+        let addrs = (host, 0)
+            .to_socket_addrs()
+            .map(|addrs| Box::new(addrs.collect::<Vec<_>>().into_iter()) as Addrs)
+            .map_err(Into::into);
+        Box::pin(std::future::ready(addrs))
+    }
+}
+
+/// Verifies server certificates against the configured roots, optionally for a different name
+/// than the URL host or not at all.
+#[derive(Debug)]
+struct Verifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    server_name: Option<ServerName<'static>>,
+    insecure_skip_verify: bool,
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        if self.insecure_skip_verify {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.webpki.verify_server_cert(
+            end_entity,
+            intermediates,
+            self.server_name.as_ref().unwrap_or(server_name),
+            ocsp_response,
+            now,
+        )
+    }
+
+    // Handshake signatures are still checked, proving the server owns the certificate it sent
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, net::TcpListener, thread};
+
+    use super::*;
+
+    fn with_server_name(server_name: &str) -> TlsConfig {
+        TlsConfig {
+            server_name: Some(server_name.to_string()),
+            insecure_skip_verify: true,
+            ..TlsConfig::default()
+        }
+    }
+
+    #[test]
+    fn request_target_uses_server_name() {
+        let tls = with_server_name("gateway.internal");
+        let url = "https://10.0.0.5:8443/api/v1/write".parse().unwrap();
+        let (target, host) = request_target(&tls, &url).unwrap();
+        assert_eq!(
+            target.as_str(),
+            "https://gateway.internal:8443/api/v1/write"
+        );
+        assert_eq!(host, "10.0.0.5:8443");
+
+        let url = "https://[::1]/write".parse().unwrap();
+        let (target, host) = request_target(&tls, &url).unwrap();
+        assert_eq!(target.as_str(), "https://gateway.internal/write");
+        assert_eq!(host, "[::1]");
+
+        // Nothing to change without TLS or a server name
+        assert!(request_target(&tls, &"http://10.0.0.5/write".parse().unwrap()).is_none());
+        assert!(request_target(&TlsConfig::default(), &url).is_none());
+    }
+
+    #[test]
+    fn sends_server_name_as_sni() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}/write", listener.local_addr().unwrap());
+        let tls = with_server_name("gateway.internal");
+        let mut client = HttpClient::new("test", &url, tls.clone()).unwrap();
+        let (target, host) = request_target(&tls, &url.parse().unwrap()).unwrap();
+        let request = client
+            .get()
+            .post(target)
+            .header(reqwest::header::HOST, host);
+        let handle = thread::spawn(move || request.send());
+
+        // The client hello names the server in plain text, which only arrives here if the
+        // server name resolved to the URL host
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut hello = vec![0; 4096];
+        let len = stream.read(&mut hello).unwrap();
+        drop(stream);
+        assert!(
+            hello[..len]
+                .windows(b"gateway.internal".len())
+                .any(|window| window == b"gateway.internal")
+        );
+        assert!(handle.join().unwrap().is_err());
+    }
+}